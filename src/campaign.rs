@@ -0,0 +1,274 @@
+use std::path::{Component, Path, PathBuf};
+
+use bevy::asset::io::{AssetSource, AssetSourceBuilder};
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::level_map;
+use crate::migration;
+use crate::sound::SoundRegistry;
+use crate::tile::TileRegistry;
+use crate::tiled;
+use crate::{LevelName, LevelScene};
+
+pub struct CampaignPlugin;
+
+impl Plugin for CampaignPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Campaigns::load());
+    }
+}
+
+/// Asset source for files from level packs, as in `mods://my_pack/music/song.ogg`
+pub const MODS_SOURCE: &str = "mods";
+
+/// Level packs are folders in `mods/` next to the game, or wherever `DGDARC_MODS_DIR` points
+pub fn mods_dir() -> PathBuf {
+    let dir = std::env::var_os("DGDARC_MODS_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("mods"));
+    // the asset server would look next to the executable instead
+    std::env::current_dir()
+        .map(|current| current.join(&dir))
+        .unwrap_or(dir)
+}
+
+/// Has to be registered before the asset plugin is added
+pub fn mods_source() -> AssetSourceBuilder {
+    AssetSource::build().with_reader(AssetSource::get_default_reader(
+        mods_dir().to_string_lossy().into_owned(),
+    ))
+}
+
+/// The `pack.toml` at the root of a level pack
+#[derive(Debug, Deserialize)]
+struct PackManifest {
+    name: LevelName,
+    /// Level files in the order they are played, relative to the pack. Maps made in Tiled
+    /// can be listed as they are.
+    levels: Vec<String>,
+}
+
+/// A list of levels played one after another
+#[derive(Debug)]
+pub struct Campaign {
+    /// Folder name of the pack, or `main` for the levels that come with the game,
+    /// progress is saved under it
+    pub id: String,
+    pub name: LevelName,
+    pub levels: Vec<LevelScene>,
+    /// The built-in tiles, with those of the pack added
+    pub tiles: TileRegistry,
+    /// The built-in sound effects, with those of the pack added and their files in the pack
+    pub sounds: SoundRegistry,
+    /// Folder of the pack, `None` for the built-in levels
+    dir: Option<PathBuf>,
+}
+
+impl Campaign {
+    pub const MAIN: &'static str = "main";
+
+    /// Level `level`, counting from 1
+    pub fn level(&self, level: usize) -> Option<&LevelScene> {
+        self.levels.get(level.checked_sub(1)?)
+    }
+
+    /// Path for the asset server, a file in the pack is used over a built-in one at the same path
+    pub fn asset_path(&self, path: &str) -> String {
+        match &self.dir {
+            Some(dir) if is_inside_folder(path) && dir.join(path).is_file() => {
+                format!("{MODS_SOURCE}://{}/{path}", self.id)
+            }
+            _ => path.to_string(),
+        }
+    }
+}
+
+/// Only plain relative paths, so a pack can't point at files outside of its own folder
+fn is_inside_folder(path: &str) -> bool {
+    Path::new(path)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+}
+
+/// The built-in levels first, then every level pack in the mods folder
+#[derive(Resource, Debug, Deref)]
+pub struct Campaigns(Vec<Campaign>);
+
+impl Campaigns {
+    fn load() -> Self {
+        let tiles = TileRegistry::built_in();
+        let levels = BUILT_IN_LEVELS
+            .iter()
+            .map(|data| parse_level(data, &tiles).unwrap())
+            .collect();
+        let main = Campaign {
+            id: Campaign::MAIN.to_string(),
+            name: LevelName::Key {
+                key: "level_select.main_campaign".to_string(),
+            },
+            levels,
+            tiles,
+            sounds: SoundRegistry::built_in(),
+            dir: None,
+        };
+        let mut campaigns = vec![main];
+        campaigns.extend(load_packs(&mods_dir()));
+        for (index, campaign) in campaigns.iter_mut().enumerate() {
+            for (level, scene) in (1..).zip(campaign.levels.iter_mut()) {
+                scene.campaign = index;
+                scene.level = level;
+            }
+        }
+        Self(campaigns)
+    }
+
+    /// A fresh copy of a level, to be played
+    pub fn scene(&self, campaign: usize, level: usize) -> Option<LevelScene> {
+        self.get(campaign)?.level(level).cloned()
+    }
+}
+
+pub fn parse_level(data: &str, tiles: &TileRegistry) -> Result<LevelScene, String> {
+    let data = migration::migrate_str(data)?;
+    let scene = toml::from_str::<LevelScene>(&data).map_err(|err| err.to_string())?;
+    prepare_level(scene, tiles)
+}
+
+/// Most tiles one line or rect in `points_of_interest` may cover, far more than fit on screen, so
+/// a typo in a corner can't stall the game while it lists billions of positions
+const MAX_SHAPE_TILES: u64 = 1_000_000;
+
+/// Fills in `points_of_interest_map`, tile names are looked up here so a typo is found when the
+/// level is loaded
+pub fn prepare_level(mut scene: LevelScene, tiles: &TileRegistry) -> Result<LevelScene, String> {
+    level_map::read_map(&mut scene, tiles)?;
+    for point in scene.points_of_interest.iter() {
+        let name = point.tile();
+        let tile = tiles
+            .get(name)
+            .ok_or_else(|| format!("there is no tile called {name:?}"))?;
+        if point.size() > MAX_SHAPE_TILES {
+            return Err(format!(
+                "a {name} shape covers {} tiles, more than the {MAX_SHAPE_TILES} allowed",
+                point.size()
+            ));
+        }
+        for position in point.positions() {
+            scene.points_of_interest_map.insert(position, tile);
+        }
+    }
+    if let Some(index) = scene.heros.iter().position(|hero| hero.targets.is_empty()) {
+        return Err(format!("hero {} has no targets", index + 1));
+    }
+    Ok(scene)
+}
+
+/// Packs that can't be read are left out with a warning, so one broken pack doesn't take the
+/// others down with it
+fn load_packs(dir: &Path) -> Vec<Campaign> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        info!("no level packs in {}", dir.display());
+        return Vec::new();
+    };
+    let mut folders: Vec<_> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.join("pack.toml").is_file())
+        .collect();
+    folders.sort();
+    folders
+        .into_iter()
+        .filter_map(|folder| match load_pack(&folder) {
+            Ok(campaign) => Some(campaign),
+            Err(err) => {
+                warn!("couldn't load level pack {}: {err}", folder.display());
+                None
+            }
+        })
+        .collect()
+}
+
+fn load_pack(folder: &Path) -> Result<Campaign, String> {
+    let read = |path: &Path| {
+        std::fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))
+    };
+    let manifest: PackManifest =
+        toml::from_str(&read(&folder.join("pack.toml"))?).map_err(|err| err.to_string())?;
+    let mut tiles = TileRegistry::built_in();
+    let tiles_file = folder.join("tiles.toml");
+    if tiles_file.is_file() {
+        tiles
+            .extend(&read(&tiles_file)?)
+            .map_err(|err| format!("tiles.toml: {err}"))?;
+    }
+    let mut sounds = SoundRegistry::built_in();
+    let sounds_file = folder.join("sounds.toml");
+    if sounds_file.is_file() {
+        sounds
+            .extend(&read(&sounds_file)?)
+            .map_err(|err| format!("sounds.toml: {err}"))?;
+    }
+    let levels = manifest
+        .levels
+        .iter()
+        .map(|file| {
+            if !is_inside_folder(file) {
+                return Err(format!("{file:?} is not a path inside the pack"));
+            }
+            let path = folder.join(file);
+            if tiled::is_tiled_map(&path) {
+                tiled::import(&path, &tiles)
+            } else {
+                parse_level(&read(&path)?, &tiles)
+            }
+            .map_err(|err| format!("{file}: {err}"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if levels.is_empty() {
+        return Err("it has no levels".to_string());
+    }
+    let outside = levels
+        .iter()
+        .flat_map(|scene| {
+            [&scene.background_texture, &scene.music]
+                .into_iter()
+                .chain(scene.music_layers.iter().map(|layer| &layer.track))
+        })
+        .chain(tiles.iter().filter_map(|kind| kind.sprite.as_ref()))
+        .chain(sounds.effects.values().map(|effect| &effect.file))
+        .find(|path| !is_inside_folder(path))
+        .cloned();
+    if let Some(path) = outside {
+        return Err(format!("{path:?} is not a path inside the pack"));
+    }
+    let id = folder
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or("it has no folder name")?;
+    let mut campaign = Campaign {
+        id,
+        name: manifest.name,
+        levels,
+        tiles,
+        sounds,
+        dir: Some(folder.to_path_buf()),
+    };
+    let files: Vec<_> = campaign
+        .sounds
+        .effects
+        .values()
+        .map(|effect| campaign.asset_path(&effect.file))
+        .collect();
+    for (effect, file) in campaign.sounds.effects.values_mut().zip(files) {
+        effect.file = file;
+    }
+    Ok(campaign)
+}
+
+pub const BUILT_IN_LEVELS: [&str; 4] = [
+    include_str!("../levels/level1.toml"),
+    include_str!("../levels/level2.toml"),
+    include_str!("../levels/level3.toml"),
+    include_str!("../levels/level4.toml"),
+];
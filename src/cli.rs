@@ -0,0 +1,129 @@
+use std::path::Path;
+
+use crate::campaign::parse_level;
+use crate::level_map;
+use crate::migration;
+use crate::tile::TileRegistry;
+use crate::tiled;
+use crate::LevelScene;
+
+const USAGE: &str = "usage: dgdarc level <map|list|migrate> <level.toml>...
+       dgdarc level import <map.tmx|map.tmj> <level.toml>
+  map      rewrite the levels with their tiles drawn in a map
+  list     rewrite the levels with their tiles in a points_of_interest list
+  migrate  upgrade the levels to the latest format_version, keeping comments
+  import   write a level made from a Tiled map
+Map and list lose the comments in the files. Tiles from a tiles.toml next to a level can be used.";
+
+/// Runs the `level` command given on the command line, `None` when there is none and the game
+/// should start. Other arguments are left alone, a launcher may pass some of its own.
+/// Gives the exit code of the command otherwise.
+pub fn run() -> Option<i32> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (command, files) = match args.as_slice() {
+        [level, command, files @ ..] if level == "level" && !files.is_empty() => {
+            (command.as_str(), files)
+        }
+        [level, ..] if level == "level" => {
+            eprintln!("{USAGE}");
+            return Some(2);
+        }
+        _ => return None,
+    };
+    if command == "import" {
+        let [map, level] = files else {
+            eprintln!("{USAGE}");
+            return Some(2);
+        };
+        return Some(match import_level(Path::new(map), Path::new(level)) {
+            Ok(()) => {
+                println!("wrote {level}");
+                0
+            }
+            Err(err) => {
+                eprintln!("{map}: {err}");
+                1
+            }
+        });
+    }
+    let rewrite: fn(&Path) -> Result<(), String> = match command {
+        "map" => |path| rewrite_level(path, level_map::to_map_form),
+        "list" => |path| {
+            rewrite_level(path, |scene, tiles| {
+                level_map::to_list_form(scene, tiles);
+                Ok(())
+            })
+        },
+        "migrate" => migrate_level,
+        _ => {
+            eprintln!("{USAGE}");
+            return Some(2);
+        }
+    };
+    let mut code = 0;
+    for file in files {
+        match rewrite(Path::new(file)) {
+            Ok(()) => println!("rewrote {file}"),
+            Err(err) => {
+                eprintln!("{file}: {err}");
+                code = 1;
+            }
+        }
+    }
+    Some(code)
+}
+
+/// The level may be in a level pack with tiles of its own
+fn tiles_next_to(path: &Path) -> Result<TileRegistry, String> {
+    let mut tiles = TileRegistry::built_in();
+    if let Some(tiles_file) = path.parent().map(|dir| dir.join("tiles.toml")) {
+        if tiles_file.is_file() {
+            let data = std::fs::read_to_string(&tiles_file).map_err(|err| err.to_string())?;
+            tiles
+                .extend(&data)
+                .map_err(|err| format!("tiles.toml: {err}"))?;
+        }
+    }
+    Ok(tiles)
+}
+
+fn rewrite_level(
+    path: &Path,
+    convert: fn(&mut LevelScene, &TileRegistry) -> Result<(), String>,
+) -> Result<(), String> {
+    let tiles = tiles_next_to(path)?;
+    let data = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let mut scene = parse_level(&data, &tiles)?;
+    convert(&mut scene, &tiles)?;
+    std::fs::write(path, level_to_toml(scene)?).map_err(|err| err.to_string())
+}
+
+/// Tiled maps are grids already, so the level gets a map too
+fn import_level(map: &Path, level: &Path) -> Result<(), String> {
+    let tiles = tiles_next_to(map)?;
+    let mut scene = tiled::import(map, &tiles)?;
+    level_map::to_map_form(&mut scene, &tiles)?;
+    std::fs::write(level, level_to_toml(scene)?).map_err(|err| err.to_string())
+}
+
+/// Works on the text, so nothing but the version and what the migrations change is touched
+fn migrate_level(path: &Path) -> Result<(), String> {
+    let data = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let migrated = migration::migrate_str(&data)?;
+    if migrated != data {
+        std::fs::write(path, migrated).map_err(|err| err.to_string())?;
+    }
+    Ok(())
+}
+
+/// Like `toml::to_string`, but with the map written out over several lines so it can be read
+fn level_to_toml(mut scene: LevelScene) -> Result<String, String> {
+    let map = scene.map.take();
+    let mut data = toml::to_string(&scene).map_err(|err| err.to_string())?;
+    if let Some(map) = map {
+        // with the other keys, before the first table
+        let tables = data.find("\n\n[").map_or(data.len(), |index| index + 1);
+        data.insert_str(tables, &format!("map = '''\n{map}'''\n"));
+    }
+    Ok(data)
+}
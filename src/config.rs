@@ -0,0 +1,39 @@
+use std::path::PathBuf;
+
+use bevy::log::{info, warn};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Settings live in `config/` next to the game, or wherever `DGDARC_CONFIG_DIR` points
+pub fn config_dir() -> PathBuf {
+    std::env::var_os("DGDARC_CONFIG_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("config"))
+}
+
+/// Reads a config file, falling back to the default when it's missing or broken
+pub fn load<T: DeserializeOwned + Default>(file_name: &str) -> T {
+    let path = config_dir().join(file_name);
+    let Ok(data) = std::fs::read_to_string(&path) else {
+        info!("no config at {}, using defaults", path.display());
+        return T::default();
+    };
+    toml::from_str(&data).unwrap_or_else(|err| {
+        warn!("couldn't read {}, using defaults: {err}", path.display());
+        T::default()
+    })
+}
+
+pub fn save<T: Serialize>(file_name: &str, value: &T) {
+    let dir = config_dir();
+    let path = dir.join(file_name);
+    let result = toml::to_string_pretty(value)
+        .map_err(|err| err.to_string())
+        .and_then(|data| {
+            std::fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+            std::fs::write(&path, data).map_err(|err| err.to_string())
+        });
+    if let Err(err) = result {
+        warn!("couldn't save {}: {err}", path.display());
+    }
+}
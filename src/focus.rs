@@ -0,0 +1,188 @@
+use bevy::prelude::*;
+use bevy::ui::UiSystem;
+
+use crate::game;
+use crate::input::{ActionState, InputAction};
+use crate::settings::{self, GameSettings};
+
+pub struct FocusPlugin;
+
+impl Plugin for FocusPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Focused>()
+            .add_systems(
+                PreUpdate,
+                (
+                    focus_hovered,
+                    (move_focus, press_focused)
+                        .chain()
+                        // the keys for menus also do things in the game, like Enter and Tab
+                        .run_if(not(game::is_playing)),
+                )
+                    .chain()
+                    .after(UiSystem::Focus)
+                    // the keys that move the focus might be the ones being bound
+                    .run_if(not(settings::is_rebinding)),
+            )
+            .add_systems(Update, outline_focused);
+    }
+}
+
+/// Buttons with this component can be reached with the keyboard and the d-pad
+#[derive(Component)]
+pub struct Focusable;
+
+/// Overlays put this on their root so only their own buttons can get the focus,
+/// when there are multiple the one with the highest number wins
+#[derive(Component, Debug, Clone, Copy)]
+pub struct FocusScope(pub i32);
+
+/// The button that gets pressed by `InputAction::MenuConfirm`
+#[derive(Resource, Debug, Default)]
+pub struct Focused(pub Option<Entity>);
+
+/// Hovering a button with the mouse moves the focus there too
+fn focus_hovered(
+    query: Query<(Entity, &Interaction), (Changed<Interaction>, With<Focusable>)>,
+    mut focused: ResMut<Focused>,
+) {
+    for (entity, interaction) in query.iter() {
+        if *interaction == Interaction::Hovered && focused.0 != Some(entity) {
+            focused.0 = Some(entity);
+        }
+    }
+}
+
+fn move_focus(
+    actions: Res<ActionState>,
+    keys: Res<ButtonInput<KeyCode>>,
+    focusables: Query<(Entity, &GlobalTransform, &ViewVisibility), With<Focusable>>,
+    scopes: Query<(Entity, &FocusScope)>,
+    parents: Query<&Parent>,
+    mut focused: ResMut<Focused>,
+) {
+    enum Move {
+        Direction(Vec2),
+        Next,
+        Previous,
+    }
+    // ui y points down
+    let movement = if actions.just_pressed(InputAction::MenuUp) {
+        Move::Direction(Vec2::NEG_Y)
+    } else if actions.just_pressed(InputAction::MenuDown) {
+        Move::Direction(Vec2::Y)
+    } else if actions.just_pressed(InputAction::MenuLeft) {
+        Move::Direction(Vec2::NEG_X)
+    } else if actions.just_pressed(InputAction::MenuRight) {
+        Move::Direction(Vec2::X)
+    } else if actions.just_pressed(InputAction::MenuNext) {
+        if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+            Move::Previous
+        } else {
+            Move::Next
+        }
+    } else {
+        return;
+    };
+
+    let scope = scopes
+        .iter()
+        .max_by_key(|(_, scope)| scope.0)
+        .map(|(entity, _)| entity);
+    let in_scope = |entity| {
+        let Some(scope) = scope else {
+            return true;
+        };
+        entity == scope || parents.iter_ancestors(entity).any(|parent| parent == scope)
+    };
+    // in reading order, which is also the order for tab
+    let mut candidates: Vec<_> = focusables
+        .iter()
+        .filter(|(entity, _, visibility)| visibility.get() && in_scope(*entity))
+        .map(|(entity, transform, _)| (entity, transform.translation().xy()))
+        .collect();
+    candidates.sort_by(|(_, a), (_, b)| a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x)));
+
+    let current = focused
+        .0
+        .and_then(|entity| candidates.iter().position(|(other, _)| *other == entity));
+    let Some(current) = current else {
+        focused.0 = candidates.first().map(|(entity, _)| *entity);
+        return;
+    };
+    let next = match movement {
+        Move::Next => Some((current + 1) % candidates.len()),
+        Move::Previous => Some((current + candidates.len() - 1) % candidates.len()),
+        Move::Direction(direction) => {
+            // the closest button in that direction, staying in the same row or column is cheaper
+            let from = candidates[current].1;
+            candidates
+                .iter()
+                .enumerate()
+                .filter_map(|(index, (_, position))| {
+                    let offset = *position - from;
+                    let along = offset.dot(direction);
+                    let across = offset.perp_dot(direction).abs();
+                    (along > 1.0).then_some((index, along + across * 2.0))
+                })
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(index, _)| index)
+        }
+    };
+    if let Some(next) = next {
+        focused.0 = Some(candidates[next].0);
+    }
+}
+
+/// Pretends the focused button got clicked, for a single frame
+fn press_focused(
+    actions: Res<ActionState>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    focused: Res<Focused>,
+    mut interactions: Query<&mut Interaction, With<Focusable>>,
+    mut pressed: Local<Option<Entity>>,
+) {
+    if let Some(entity) = pressed.take() {
+        if let Ok(mut interaction) = interactions.get_mut(entity) {
+            if *interaction == Interaction::Pressed && !mouse.pressed(MouseButton::Left) {
+                *interaction = Interaction::None;
+            }
+        }
+    }
+    if !actions.just_pressed(InputAction::MenuConfirm) {
+        return;
+    }
+    let Some(entity) = focused.0 else {
+        return;
+    };
+    if let Ok(mut interaction) = interactions.get_mut(entity) {
+        *interaction = Interaction::Pressed;
+        *pressed = Some(entity);
+    }
+}
+
+/// Also runs when a focused button gets spawned, since menus are often rebuilt,
+/// and when the settings change since the palette gives the colour of the ring
+fn outline_focused(
+    mut commands: Commands,
+    focused: Res<Focused>,
+    settings: Res<GameSettings>,
+    outlined: Query<Entity, (With<Focusable>, With<Outline>)>,
+    added: Query<(), Added<Focusable>>,
+) {
+    let newly_spawned = focused.0.is_some_and(|entity| added.contains(entity));
+    if !focused.is_changed() && !newly_spawned && !settings.is_changed() {
+        return;
+    }
+    for entity in outlined.iter() {
+        if Some(entity) != focused.0 {
+            commands.entity(entity).remove::<Outline>();
+        }
+    }
+    if let Some(entity) = focused.0 {
+        if let Some(mut entity) = commands.get_entity(entity) {
+            let color = settings.palette.colors().focus;
+            entity.insert(Outline::new(Val::Px(4.0), Val::ZERO, color));
+        }
+    }
+}
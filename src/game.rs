@@ -1,25 +1,66 @@
 use std::f32::consts::FRAC_PI_2;
 use std::time::Duration;
 
-use bevy::audio::{PlaybackMode, Volume};
-use bevy::input::mouse::MouseWheel;
 use bevy::sprite::Anchor;
 use bevy::{prelude::*, window::PrimaryWindow};
-
-use crate::level_select::{LevelSelectWindow, LevelsWon, ReenterLevel};
-use crate::tile::make_tile;
-use crate::{despawn_screen, EndGameEvent, GameState, LevelScene, BGM};
+use serde::Deserialize;
+
+use crate::campaign::Campaigns;
+use crate::config;
+use crate::input::{ActionState, GameCursor, InputAction};
+use crate::level_select::LevelProgress;
+use crate::locale::LocalizedText;
+use crate::music::{MusicLayer, MusicMood, PlayMusic};
+use crate::palette::PaletteColors;
+use crate::settings::{GameSettings, SettingsState};
+use crate::sound::{PlaySound, SoundRegistry};
+use crate::tile::{grid_to_world, world_to_grid, TileEntities};
+use crate::widget::{ButtonColors, SpawnButton};
+use crate::{despawn_screen, EndGameEvent, GameState, LevelScene};
+use camera::*;
+use damage::*;
 use hero::*;
-
+use history::*;
+use mood::*;
+use pause::*;
+use planning::*;
+use speed::*;
+use tilemap::*;
+
+pub mod camera;
+pub mod damage;
 pub mod hero;
+pub mod history;
+pub mod mood;
+pub mod pause;
+pub mod planning;
+pub mod speed;
+pub mod tilemap;
 
 pub struct GamePlugin;
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<Spell>()
+            .init_resource::<TileEntities>()
+            .init_resource::<AttemptHistory>()
+            .init_resource::<GameSpeed>()
+            .init_resource::<LevelBounds>()
             .init_state::<GameRunning>()
-            .add_systems(OnEnter(GameState::Gaming), (setup, create_hero))
+            .add_event::<DamageEvent>()
+            .add_event::<HealEvent>()
+            .add_event::<HeroDied>()
+            .add_event::<CastSpell>()
+            .add_systems(
+                OnEnter(GameState::Gaming),
+                (
+                    setup,
+                    spawn_tiles,
+                    create_hero,
+                    apply_game_speed.after(setup),
+                    highlight_selected_speed.after(setup),
+                ),
+            )
             .add_systems(
                 Update,
                 (
@@ -29,35 +70,146 @@ impl Plugin for GamePlugin {
                     animate_and_despawn_fire.run_if(in_state(GameState::Gaming)),
                     animate_and_despawn_healing.run_if(in_state(GameState::Gaming)),
                     animate_and_despawn_gust.run_if(in_state(GameState::Gaming)),
-                    cast_spell.run_if(
-                        in_state(GameState::Gaming).and_then(in_state(GameRunning::Running)),
-                    ),
                     move_heros.run_if(
                         in_state(GameState::Gaming).and_then(in_state(GameRunning::Running)),
                     ),
+                    apply_health_events
+                        .run_if(
+                            in_state(GameState::Gaming).and_then(in_state(GameRunning::Running)),
+                        )
+                        .after(move_heros),
                     update_health_bars
                         .run_if(
                             in_state(GameState::Gaming).and_then(in_state(GameRunning::Running)),
                         )
+                        .after(apply_health_events),
+                    record_history
+                        .run_if(
+                            in_state(GameState::Gaming).and_then(in_state(GameRunning::Running)),
+                        )
+                        .after(apply_health_events),
+                    move_camera.run_if(in_state(GameState::Gaming).and_then(
+                        in_state(GameRunning::Running).or_else(in_state(GameRunning::Planning)),
+                    )),
+                    wait_to_go_back.run_if(
+                        in_state(GameState::Gaming).and_then(in_state(GameRunning::AfterEnd)),
+                    ),
+                    cycle_followed_hero.run_if(in_state(GameState::Gaming).and_then(
+                        in_state(GameRunning::Running).or_else(in_state(GameRunning::Planning)),
+                    )),
+                    follow_hero
+                        .run_if(in_state(GameState::Gaming))
+                        .after(move_camera)
+                        .after(cycle_followed_hero)
                         .after(move_heros),
-                    move_camera.run_if(
+                    smooth_camera
+                        .run_if(in_state(GameState::Gaming))
+                        .after(follow_hero),
+                    toggle_pause.run_if(
+                        in_state(GameState::Gaming).and_then(in_state(SettingsState::Closed)),
+                    ),
+                    select_speed_button.run_if(in_state(GameState::Gaming).and_then(
+                        in_state(GameRunning::Running).or_else(in_state(GameRunning::Planning)),
+                    )),
+                    select_speed_keybind.run_if(in_state(GameState::Gaming).and_then(
+                        in_state(GameRunning::Running).or_else(in_state(GameRunning::Planning)),
+                    )),
+                    apply_game_speed.run_if(
+                        in_state(GameState::Gaming).and_then(resource_changed::<GameSpeed>),
+                    ),
+                    pause_menu_pressed.run_if(
+                        in_state(GameState::Gaming).and_then(in_state(GameRunning::Paused)),
+                    ),
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    cast_spell.run_if(in_state(GameState::Gaming).and_then(
+                        in_state(GameRunning::Running).or_else(in_state(GameRunning::Planning)),
+                    )),
+                    cast_planned_spells.run_if(
                         in_state(GameState::Gaming).and_then(in_state(GameRunning::Running)),
                     ),
-                    wait_to_go_back.run_if(
-                        in_state(GameState::Gaming).and_then(in_state(GameRunning::AfterEnd)),
+                    spawn_spells
+                        .run_if(in_state(GameState::Gaming))
+                        .after(cast_spell)
+                        .after(cast_planned_spells),
+                    toggle_planning.run_if(in_state(GameState::Gaming).and_then(
+                        in_state(GameRunning::Running).or_else(in_state(GameRunning::Planning)),
+                    )),
+                    undo_planned_spell.run_if(
+                        in_state(GameState::Gaming).and_then(in_state(GameRunning::Planning)),
+                    ),
+                    cycle_spell.run_if(in_state(GameState::Gaming)),
+                    play_hazard_sounds.run_if(
+                        in_state(GameState::Gaming).and_then(in_state(GameRunning::Running)),
                     ),
+                    update_music_mood.run_if(
+                        in_state(GameState::Gaming).and_then(in_state(GameRunning::Running)),
+                    ),
+                    pause_on_focus_loss.run_if(
+                        in_state(GameState::Gaming).and_then(in_state(GameRunning::Running)),
+                    ),
+                    highlight_selected_speed.run_if(in_state(GameState::Gaming).and_then(
+                        resource_changed::<GameSpeed>.or_else(resource_changed::<GameSettings>),
+                    )),
                 ),
             )
+            .add_systems(
+                OnEnter(GameRunning::Planning),
+                (freeze_time, spawn_planning_hint),
+            )
+            .add_systems(
+                OnExit(GameRunning::Planning),
+                (unfreeze_time, despawn_screen::<PlanningHint>),
+            )
+            .add_systems(
+                OnEnter(GameRunning::Paused),
+                (freeze_time, spawn_pause_menu),
+            )
+            .add_systems(
+                OnExit(GameRunning::Paused),
+                (unfreeze_time, despawn_screen::<PauseMenu>),
+            )
+            .add_systems(
+                OnEnter(GameRunning::Restarting),
+                (
+                    despawn_screen::<GameWindow>,
+                    setup,
+                    spawn_tiles,
+                    create_hero,
+                    apply_game_speed,
+                    highlight_selected_speed,
+                )
+                    .chain(),
+            )
             .add_systems(PostUpdate, register_win.run_if(in_state(GameState::Gaming)))
-            .add_systems(OnExit(GameState::Gaming), despawn_screen::<GameWindow>);
+            .add_systems(
+                OnExit(GameState::Gaming),
+                (despawn_screen::<GameWindow>, reset_game_speed),
+            );
     }
 }
 
+/// Run condition for a level that is being played, when the input goes to the game and not
+/// to the buttons of a menu
+pub fn is_playing(
+    game_state: Res<State<GameState>>,
+    running_state: Res<State<GameRunning>>,
+) -> bool {
+    *game_state.get() == GameState::Gaming
+        && matches!(
+            running_state.get(),
+            GameRunning::Running | GameRunning::Planning
+        )
+}
+
 /// Annotate everything specific to the game window with this component
 #[derive(Component)]
 pub struct GameWindow;
 
-#[derive(Component, Resource, Default, Debug, Clone, Copy, PartialEq)]
+#[derive(Component, Resource, Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
 pub enum Spell {
     #[default]
     None,
@@ -70,7 +222,12 @@ pub enum Spell {
 pub enum GameRunning {
     #[default]
     Running,
+    Paused,
+    /// Frozen while the player places spells that get cast once the plan is released
+    Planning,
     AfterEnd,
+    /// Passed through for a single frame to set the level up again from scratch
+    Restarting,
 }
 
 #[derive(Component, Clone, PartialEq)]
@@ -100,42 +257,39 @@ fn setup(
     mut running_state: ResMut<NextState<GameRunning>>,
     asset_server: Res<AssetServer>,
     scene: Res<LevelScene>,
+    campaigns: Res<Campaigns>,
     selected_spell: ResMut<Spell>,
+    mut history: ResMut<AttemptHistory>,
     window: Query<&Window, With<PrimaryWindow>>,
-    mut bgm_query: Query<(&mut BGM, Entity)>,
+    mut music: EventWriter<PlayMusic>,
+    mut mood: ResMut<MusicMood>,
 ) {
-    commands.spawn((
-        Camera2dBundle {
-            camera: Camera {
-                clear_color: ClearColorConfig::Custom(Color::Rgba {
-                    red: 0.3,
-                    green: 1.0,
-                    blue: 1.0,
-                    alpha: 0.0,
-                }),
-                ..default()
-            },
-            ..default()
-        },
-        GameWindow,
-    ));
-
     let resolution = &window.single().resolution;
-
+    let window_size = Vec2::new(resolution.width(), resolution.height());
+    let bounds = LevelBounds::from_scene(&scene);
+    // a level pack can bring its own background and music
+    let campaign = &campaigns[scene.campaign];
+    commands.insert_resource(campaign.sounds.clone());
+    let camera = spawn_game_camera(&mut commands, &bounds, window_size);
+    commands.entity(camera).insert(GameWindow);
+
+    // cover everything the camera can show when zoomed out all the way
+    let background_size = bounds.with_margin().size() + window_size * bounds.max_scale(window_size);
+    let background_center = grid_to_world(world_to_grid(bounds.center()));
     commands.spawn((
         SpriteBundle {
-            texture: asset_server.load(&scene.background_texture),
+            texture: asset_server.load(campaign.asset_path(&scene.background_texture)),
             transform: Transform {
                 scale: Vec3::splat(4.0),
                 translation: Vec3 {
-                    x: 8.0,
-                    y: 8.0,
+                    x: background_center.x + 8.0,
+                    y: background_center.y + 8.0,
                     z: 0.1,
                 },
                 ..default()
             },
             sprite: Sprite {
-                custom_size: Some(Vec2::new(resolution.width(), resolution.height())),
+                custom_size: Some((background_size / 4.0 / 16.0).ceil() * 16.0),
                 ..default()
             },
             ..default()
@@ -147,17 +301,19 @@ fn setup(
         },
         GameWindow,
     ));
+    commands.insert_resource(bounds);
 
-    if let Ok((mut bgm, entity)) = bgm_query.get_single_mut() {
-        let music = format!("music/{}", scene.music);
-        if bgm.0 != music {
-            commands
-                .entity(entity)
-                .remove::<AudioSink>()
-                .insert(asset_server.load::<AudioSource>(&music));
-            bgm.0 = music;
-        }
-    }
+    *mood = MusicMood::Calm;
+    let layers = scene
+        .music_layers
+        .iter()
+        .map(|layer| MusicLayer {
+            track: campaign.asset_path(&format!("music/{}", layer.track)),
+            mood: layer.mood,
+        })
+        .collect();
+    let track = campaign.asset_path(&format!("music/{}", scene.music));
+    music.send(PlayMusic::looping(track).with_layers(layers));
 
     commands
         .spawn((
@@ -180,8 +336,9 @@ fn setup(
                     ButtonBundle {
                         style: Style {
                             align_self: AlignSelf::Center,
-                            min_width: Val::Vw(6.0),
-                            min_height: Val::Vw(6.0),
+                            // in pixels, so the interface size setting applies to it
+                            min_width: Val::Px(100.0),
+                            min_height: Val::Px(100.0),
                             display: Display::Flex,
                             border: UiRect::all(Val::Px(5.0)),
                             margin: UiRect::all(Val::Px(3.0)),
@@ -199,10 +356,9 @@ fn setup(
                 ));
             }
         });
-    for (position, tile) in scene.points_of_interest.iter() {
-        make_tile(*tile, *position, &mut commands, &asset_server)
-    }
+    spawn_speed_buttons(&mut commands);
 
+    *history = AttemptHistory::default();
     *selected_spell.into_inner() = Spell::None;
     running_state.set(GameRunning::Running);
 }
@@ -215,7 +371,7 @@ fn select_spell_button(query: Query<(&Interaction, &Spell)>, mut selected_spell:
     }
 }
 
-fn select_spell_keybind(input: Res<ButtonInput<KeyCode>>, mut selected_spell: ResMut<Spell>) {
+fn select_spell_keybind(actions: Res<ActionState>, mut selected_spell: ResMut<Spell>) {
     let mut select_spell = |spell| {
         *selected_spell = if selected_spell.as_ref() == &spell {
             Spell::None
@@ -223,47 +379,94 @@ fn select_spell_keybind(input: Res<ButtonInput<KeyCode>>, mut selected_spell: Re
             spell
         }
     };
-    if input.any_just_pressed([KeyCode::Digit1, KeyCode::Numpad1]) {
+    if actions.just_pressed(InputAction::SelectSpell(1)) {
         select_spell(Spell::FireWall);
-    } else if input.any_just_pressed([KeyCode::Digit2, KeyCode::Numpad2]) {
+    } else if actions.just_pressed(InputAction::SelectSpell(2)) {
         select_spell(Spell::HealthBoost);
-    } else if input.any_just_pressed([KeyCode::Digit3, KeyCode::Numpad3]) {
+    } else if actions.just_pressed(InputAction::SelectSpell(3)) {
         select_spell(Spell::WindGust);
     }
 }
 
-const BORDER_HIGHLIGHT: BorderColor = BorderColor(Color::ORANGE_RED);
-const BORDER_NOT_HIGHLIGHT: BorderColor = BorderColor(Color::WHITE);
+/// Steps through the spell bar, for gamepads which don't have number keys
+fn cycle_spell(actions: Res<ActionState>, mut selected_spell: ResMut<Spell>) {
+    let spells = [Spell::FireWall, Spell::HealthBoost, Spell::WindGust];
+    let current = spells
+        .iter()
+        .position(|spell| spell == selected_spell.as_ref());
+    let next = if actions.just_pressed(InputAction::NextSpell) {
+        current.map_or(0, |index| (index + 1) % spells.len())
+    } else if actions.just_pressed(InputAction::PreviousSpell) {
+        current.map_or(spells.len() - 1, |index| {
+            (index + spells.len() - 1) % spells.len()
+        })
+    } else {
+        return;
+    };
+    *selected_spell = spells[next];
+}
+
+/// The selected button also gets a ring around its border, so it doesn't only stand out by colour
+fn highlight(
+    commands: &mut Commands,
+    entity: Entity,
+    border: &mut BorderColor,
+    selected: bool,
+    colors: &PaletteColors,
+) {
+    if selected {
+        *border = colors.selected.into();
+        commands
+            .entity(entity)
+            .insert(Outline::new(Val::Px(3.0), Val::Px(3.0), colors.selected));
+    } else {
+        *border = colors.unselected.into();
+        commands.entity(entity).remove::<Outline>();
+    }
+}
 
 fn highlight_selected_spell(
+    mut commands: Commands,
     selected_spell: Res<Spell>,
-    mut query: Query<(&Spell, &mut BorderColor)>,
+    settings: Res<GameSettings>,
+    mut query: Query<(Entity, &Spell, &mut BorderColor)>,
 ) {
-    if selected_spell.is_changed() {
-        for (spell, border) in query.iter_mut() {
-            *border.into_inner() = if spell == selected_spell.as_ref() {
-                BORDER_HIGHLIGHT
-            } else {
-                BORDER_NOT_HIGHLIGHT
-            }
+    if selected_spell.is_changed() || settings.is_changed() {
+        let colors = settings.palette.colors();
+        for (entity, spell, mut border) in query.iter_mut() {
+            let selected = spell == selected_spell.as_ref();
+            highlight(&mut commands, entity, &mut border, selected, colors);
         }
     }
 }
 
+/// A spell about to appear in the world, `direction` is only used by the wind gust
+#[derive(Event, Clone, Copy, Debug)]
+pub struct CastSpell {
+    pub spell: Spell,
+    pub position: Vec2,
+    pub direction: Vec2,
+}
+
+/// Turns clicks into spells, cast right away or planned when the game is frozen for planning
 fn cast_spell(
     mut commands: Commands,
     selected_spell: Res<Spell>,
-    window: Query<&Window, With<PrimaryWindow>>,
+    running_state: Res<State<GameRunning>>,
+    cursor: Res<GameCursor>,
     fire_walls: Query<&FireWall>,
-    input: Res<ButtonInput<MouseButton>>,
+    planned_spells: Query<&PlannedSpell>,
+    actions: Res<ActionState>,
     asset_server: Res<AssetServer>,
     camera_query: Query<(&Camera, &GlobalTransform)>,
     interaction_query: Query<&Interaction>,
     healing_spell: Query<&HealingCircle>,
     mut last_mouse_down: Local<Vec2>,
+    mut placed_spells: Local<u32>,
     mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    mut cast_writer: EventWriter<CastSpell>,
 ) {
-    let Some(mouse_position) = window.single().cursor_position() else {
+    let Some(mouse_position) = cursor.position else {
         return;
     };
     let (camera, global_transform) = camera_query.single();
@@ -271,138 +474,164 @@ fn cast_spell(
     else {
         return;
     };
-    let mouse_on_game = interaction_query
-        .iter()
-        .all(|interaction| *interaction == Interaction::None);
-    let pressed = input.pressed(MouseButton::Left) && mouse_on_game;
-    if input.just_pressed(MouseButton::Left) && mouse_on_game {
+    // the virtual cursor can't hover the buttons, so it's always on the game
+    let mouse_on_game = cursor.from_gamepad
+        || interaction_query
+            .iter()
+            .all(|interaction| *interaction == Interaction::None);
+    let pressed = actions.pressed(InputAction::Cast) && mouse_on_game;
+    if actions.just_pressed(InputAction::Cast) && mouse_on_game {
         *last_mouse_down = ingame_position
     };
-    match selected_spell.as_ref() {
-        Spell::None => {}
+    // planned spells count as cast for the placement rules
+    let mut planned = planned_spells.iter().map(|planned| planned.cast);
+    let spell = *selected_spell.as_ref();
+    let cast = match spell {
+        Spell::None => None,
         Spell::FireWall => {
-            if pressed
-                && !fire_walls
-                    .iter()
-                    .any(|wall| wall.position.distance(ingame_position) < 40.0)
-            {
-                let layout_not_fr =
-                    TextureAtlasLayout::from_grid(Vec2::new(16.0, 32.0), 10, 1, None, None);
-                let layout = texture_atlas_layouts.add(layout_not_fr);
+            let too_close = fire_walls
+                .iter()
+                .map(|wall| wall.position)
+                .chain(
+                    planned
+                        .filter(|cast| cast.spell == Spell::FireWall)
+                        .map(|cast| cast.position),
+                )
+                .any(|position| position.distance(ingame_position) < 40.0);
+            (pressed && !too_close).then_some(CastSpell {
+                spell,
+                position: ingame_position,
+                direction: Vec2::ZERO,
+            })
+        }
+        Spell::HealthBoost => {
+            let already_healing =
+                !healing_spell.is_empty() || planned.any(|cast| cast.spell == Spell::HealthBoost);
+            (pressed && !already_healing).then_some(CastSpell {
+                spell,
+                position: ingame_position,
+                direction: Vec2::ZERO,
+            })
+        }
+        Spell::WindGust => {
+            (actions.just_released(InputAction::Cast) && mouse_on_game).then_some(CastSpell {
+                spell,
+                position: *last_mouse_down,
+                direction: ingame_position - *last_mouse_down,
+            })
+        }
+    };
+    let Some(cast) = cast else {
+        return;
+    };
+    if *running_state.get() == GameRunning::Planning {
+        *placed_spells += 1;
+        plan_spell(
+            &mut commands,
+            cast,
+            *placed_spells,
+            &asset_server,
+            &mut texture_atlas_layouts,
+        );
+    } else {
+        cast_writer.send(cast);
+    }
+}
+
+/// The first frame of a spell, shared by the spell itself and its preview while planning
+pub fn spell_sprite(
+    cast: &CastSpell,
+    asset_server: &AssetServer,
+    texture_atlas_layouts: &mut Assets<TextureAtlasLayout>,
+) -> SpriteSheetBundle {
+    let (texture, tile_size, columns, z) = match cast.spell {
+        Spell::FireWall => ("FireWall.png", Vec2::new(16.0, 32.0), 10, 2.0),
+        Spell::HealthBoost => ("HealingCircle.png", Vec2::new(32.0, 32.0), 14, 0.6),
+        Spell::WindGust => ("Gust.png", Vec2::new(16.0, 32.0), 21, 3.0),
+        Spell::None => unreachable!(),
+    };
+    let layout_not_fr = TextureAtlasLayout::from_grid(tile_size, columns, 1, None, None);
+    let layout = texture_atlas_layouts.add(layout_not_fr);
+    let mut bundle = SpriteSheetBundle {
+        transform: Transform {
+            translation: cast.position.extend(z),
+            scale: Vec3::new(4.0, 4.0, 1.0),
+            ..default()
+        },
+        texture: asset_server.load(texture),
+        atlas: TextureAtlas { layout, index: 0 },
+        ..default()
+    };
+    if cast.spell == Spell::WindGust {
+        let direction = cast.direction;
+        bundle.transform.rotation =
+            Quat::from_rotation_z(direction.y.atan2(direction.x) - FRAC_PI_2);
+        bundle.sprite.anchor = Anchor::BottomCenter;
+    }
+    bundle
+}
+
+fn spawn_spells(
+    mut commands: Commands,
+    mut cast_reader: EventReader<CastSpell>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    registry: Res<SoundRegistry>,
+    mut sound_writer: EventWriter<PlaySound>,
+) {
+    for cast in cast_reader.read() {
+        let sprite = spell_sprite(cast, &asset_server, &mut texture_atlas_layouts);
+        if let Some(sound) = registry.spells.get(&cast.spell) {
+            sound_writer.send(PlaySound::at(sound.clone(), cast.position));
+        }
+        match cast.spell {
+            Spell::None => {}
+            Spell::FireWall => {
                 commands.spawn((
-                    SpriteSheetBundle {
-                        transform: Transform {
-                            translation: ingame_position.extend(2.0),
-                            scale: Vec3::splat(4.0),
-                            ..default()
-                        },
-                        texture: asset_server.load("FireWall.png"),
-                        atlas: TextureAtlas { layout, index: 0 },
-                        ..default()
-                    },
+                    sprite,
                     FireWall {
-                        position: ingame_position,
+                        position: cast.position,
                         ttl: Timer::from_seconds(5.0, TimerMode::Once),
                     },
-                    AudioBundle {
-                        source: asset_server.load("effects/firewall.ogg"),
-                        settings: PlaybackSettings {
-                            mode: PlaybackMode::Remove,
-                            volume: Volume::new(0.4),
-                            ..default()
-                        },
-                    },
                     AnimationTimer(Timer::new(Duration::from_millis(100), TimerMode::Repeating)),
                     GameWindow,
                 ));
             }
-        }
-        Spell::HealthBoost => {
-            if healing_spell.is_empty() && pressed {
-                let layout_not_fr =
-                    TextureAtlasLayout::from_grid(Vec2::new(32.0, 32.0), 14, 1, None, None);
-                let layout = texture_atlas_layouts.add(layout_not_fr);
+            Spell::HealthBoost => {
                 let healing_duration = Duration::from_secs(4);
 
                 commands.spawn((
-                    SpriteSheetBundle {
-                        transform: Transform {
-                            translation: ingame_position.extend(0.6),
-                            scale: Vec3::new(4.0, 4.0, 1.0),
-                            ..default()
-                        },
-                        texture: asset_server.load("HealingCircle.png"),
-                        atlas: TextureAtlas { layout, index: 0 },
-                        ..default()
-                    },
+                    sprite,
                     HealingCircle {
-                        position: ingame_position,
+                        position: cast.position,
                         timer: Timer::new(healing_duration, TimerMode::Once),
                     },
                     AnimationTimer(Timer::new(
                         healing_duration.mul_f32(1.0 / 14.0),
                         TimerMode::Repeating,
                     )),
-                    AudioBundle {
-                        source: asset_server.load("effects/healingSpell.ogg"),
-                        settings: PlaybackSettings {
-                            mode: PlaybackMode::Remove,
-                            // volume: Volume::new(0.4),
-                            ..default()
-                        },
-                    },
                     GameWindow,
                 ));
             }
-        }
-        Spell::WindGust => {
-            if input.just_released(MouseButton::Left) && mouse_on_game {
-                let layout_not_fr =
-                    TextureAtlasLayout::from_grid(Vec2::new(16.0, 32.0), 21, 1, None, None);
-                let layout = texture_atlas_layouts.add(layout_not_fr);
+            Spell::WindGust => {
                 let gust_duration = Duration::from_secs(2);
 
-                let direction = ingame_position - *last_mouse_down;
-
                 commands.spawn((
-                    SpriteSheetBundle {
-                        transform: Transform {
-                            translation: last_mouse_down.extend(3.0),
-                            scale: Vec3::new(4.0, 4.0, 1.0),
-                            rotation: Quat::from_rotation_z(
-                                direction.y.atan2(direction.x) - FRAC_PI_2,
-                            ),
-                        },
-                        sprite: Sprite {
-                            anchor: Anchor::BottomCenter,
-                            ..default()
-                        },
-                        texture: asset_server.load("Gust.png"),
-                        atlas: TextureAtlas { layout, index: 0 },
-                        ..default()
-                    },
+                    sprite,
                     WindGust {
-                        position: *last_mouse_down,
-                        direction: direction.normalize(),
+                        position: cast.position,
+                        direction: cast.direction.normalize(),
                         timer: Timer::new(gust_duration, TimerMode::Once),
                     },
                     AnimationTimer(Timer::new(
                         gust_duration.mul_f32(1.0 / 21.0),
                         TimerMode::Repeating,
                     )),
-                    AudioBundle {
-                        source: asset_server.load("effects/wind_spell.ogg"),
-                        settings: PlaybackSettings {
-                            mode: PlaybackMode::Remove,
-                            volume: Volume::new(0.4),
-                            ..default()
-                        },
-                    },
                     GameWindow,
                 ));
             }
-        }
-    };
+        };
+    }
 }
 
 fn animate_and_despawn_fire(
@@ -414,15 +643,17 @@ fn animate_and_despawn_fire(
         &mut AnimationTimer,
     )>,
     time: Res<Time>,
+    settings: Res<GameSettings>,
 ) {
+    // the flames flicker a lot, slow them down when flashing should be toned down
+    let animation_speed = if settings.reduce_flashing { 0.25 } else { 1.0 };
     for (entity, mut firewall, mut atlas, mut animation) in query.iter_mut() {
         firewall.ttl.tick(time.delta());
         if firewall.ttl.finished() {
             commands.entity(entity).despawn_recursive();
         }
 
-        const ANIMATION_SPEED: f32 = 1.0;
-        animation.tick(time.delta().mul_f32(ANIMATION_SPEED));
+        animation.tick(time.delta().mul_f32(animation_speed));
         if animation.just_finished() {
             atlas.index = if atlas.index == 9 { 4 } else { atlas.index + 1 }
         }
@@ -482,58 +713,28 @@ fn animate_and_despawn_gust(
     }
 }
 
-fn move_camera(
-    mut camera_query: Query<&mut Transform, With<Camera>>,
-    mut event_reader: EventReader<CursorMoved>,
-    mut scroll_event: EventReader<MouseWheel>,
-    interaction_query: Query<&Interaction>,
-    input: Res<ButtonInput<MouseButton>>,
-) {
-    for event in event_reader.read() {
-        if !interaction_query
-            .iter()
-            .all(|interaction| *interaction == Interaction::None)
-        {
-            break;
-        }
-        if input.pressed(MouseButton::Right) {
-            let mut transform = camera_query.single_mut();
-            // the constant is just a random value that felt right
-            let zoom_factor = transform.scale.x;
-            let delta = event.delta.unwrap_or_default();
-            transform.translation += Vec3::new(-delta.x, delta.y, 0.0) * zoom_factor;
-        }
-    }
-    for event in scroll_event.read() {
-        if !interaction_query
-            .iter()
-            .all(|interaction| *interaction == Interaction::None)
-        {
-            break;
-        }
-        let mut transform = camera_query.single_mut();
-        const SCROLL_SPEED: f32 = 0.1;
-        transform.scale = (transform.scale.xy()
-            - (((event.x + event.y) * SCROLL_SPEED).clamp(-1.0, 1.0)))
-        .max(Vec2::splat(0.3))
-        .extend(1.0);
-    }
+/// The buttons shown once a level is won or lost
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+enum EndButton {
+    NextLevel,
+    BackToMenu,
+    Retry,
 }
 
-#[derive(Component)]
-struct BackToMenuButton;
-#[derive(Component)]
-struct RetryButton;
-
 fn register_win(
     mut commands: Commands,
     mut event_reader: EventReader<EndGameEvent>,
+    mut died_reader: EventReader<HeroDied>,
+    heros: Query<&Hero>,
+    history: Res<AttemptHistory>,
     level: Res<LevelScene>,
-    mut levels_won: ResMut<LevelsWon>,
+    campaigns: Res<Campaigns>,
+    mut progress: ResMut<LevelProgress>,
     mut state: ResMut<NextState<GameRunning>>,
-    asset_server: Res<AssetServer>,
-    mut bgm_query: Query<(&mut BGM, Entity)>,
+    mut music: EventWriter<PlayMusic>,
+    settings: Res<GameSettings>,
 ) {
+    let colors = settings.palette.colors();
     for event in event_reader.read() {
         let style = Style {
             position_type: PositionType::Absolute,
@@ -541,33 +742,35 @@ fn register_win(
             justify_self: JustifySelf::Center,
             ..default()
         };
-        let music;
+        let stinger;
         if let EndGameEvent::Win = event {
-            levels_won[level.level - 1] = true;
+            progress.record_win(&campaigns[level.campaign], level.level, history.elapsed);
+            config::save(LevelProgress::FILE_NAME, progress.as_ref());
             commands.spawn((
                 TextBundle {
                     text: Text::from_section(
-                        "You Win!",
+                        String::new(),
                         TextStyle {
                             font_size: 100.0,
-                            color: Color::GREEN,
+                            color: colors.win,
                             ..default()
                         },
                     ),
                     style,
                     ..default()
                 },
+                LocalizedText::key("game.win"),
                 GameWindow,
             ));
-            music = "music/Victory.ogg";
+            stinger = "music/Victory.ogg";
         } else {
             commands.spawn((
                 TextBundle {
                     text: Text::from_section(
-                        "You Lose!",
+                        String::new(),
                         TextStyle {
                             font_size: 100.0,
-                            color: Color::RED,
+                            color: colors.loss,
                             ..default()
                         },
                     )
@@ -575,98 +778,82 @@ fn register_win(
                     style,
                     ..default()
                 },
+                LocalizedText::key("game.lose"),
                 GameWindow,
             ));
-            music = "music/Loss.ogg";
-        }
-        if let Ok((mut bgm, entity)) = bgm_query.get_single_mut() {
-            if bgm.0 != music {
-                commands
-                    .entity(entity)
-                    .remove::<AudioSink>()
-                    .insert(asset_server.load::<AudioSource>(music));
-                bgm.0 = music.to_string();
-            }
+            spawn_defeat_report(
+                &mut commands,
+                died_reader.read().next(),
+                &heros,
+                &history,
+                colors,
+            );
+            stinger = "music/Loss.ogg";
         }
+        music.send(PlayMusic::stinger(stinger));
+
+        let won = matches!(event, EndGameEvent::Win);
+        let has_next_level = won && campaigns[level.campaign].levels.len() > level.level;
+        let buttons = [
+            (EndButton::NextLevel, "game.next_level"),
+            (EndButton::BackToMenu, "game.back_to_menu"),
+            (EndButton::Retry, "game.retry"),
+        ];
         commands
             .spawn((
-                ButtonBundle {
+                NodeBundle {
                     style: Style {
                         position_type: PositionType::Absolute,
-                        top: Val::Percent(60.0),
+                        top: Val::Percent(55.0),
                         justify_self: JustifySelf::Center,
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        row_gap: Val::Px(20.0),
                         ..default()
                     },
-                    background_color: Color::FUCHSIA.into(),
                     ..default()
                 },
-                BackToMenuButton,
                 GameWindow,
             ))
             .with_children(|parent| {
-                parent.spawn(TextBundle {
-                    text: Text::from_section(
-                        "Go Back To Menu",
-                        TextStyle {
-                            font_size: 60.0,
-                            color: Color::WHITE,
-                            ..default()
-                        },
-                    )
-                    .with_justify(JustifyText::Center),
-                    ..default()
-                });
-            });
-        commands
-            .spawn((
-                ButtonBundle {
-                    style: Style {
-                        position_type: PositionType::Absolute,
-                        top: Val::Percent(80.0),
-                        justify_self: JustifySelf::Center,
-                        ..default()
-                    },
-                    background_color: Color::FUCHSIA.into(),
-                    ..default()
-                },
-                RetryButton,
-                GameWindow,
-            ))
-            .with_children(|parent| {
-                parent.spawn(TextBundle {
-                    text: Text::from_section(
-                        "Retry",
-                        TextStyle {
-                            font_size: 60.0,
-                            color: Color::WHITE,
-                            ..default()
-                        },
-                    )
-                    .with_justify(JustifyText::Center),
-                    ..default()
-                });
+                for (button, key) in buttons {
+                    if button == EndButton::NextLevel && !has_next_level {
+                        continue;
+                    }
+                    parent
+                        .spawn_button(
+                            LocalizedText::key(key),
+                            60.0,
+                            ButtonColors::GAME,
+                            Style::default(),
+                        )
+                        .insert(button);
+                }
             });
         state.set(GameRunning::AfterEnd);
     }
 }
 
 fn wait_to_go_back(
-    mut commands: Commands,
-    menu: Query<&Interaction, With<BackToMenuButton>>,
-    retry: Query<&Interaction, With<RetryButton>>,
-    level: Res<LevelScene>,
+    buttons: Query<(&Interaction, &EndButton)>,
+    campaigns: Res<Campaigns>,
+    mut scene: ResMut<LevelScene>,
     mut state: ResMut<NextState<GameState>>,
+    mut running_state: ResMut<NextState<GameRunning>>,
 ) {
-    for &interaction in menu.iter() {
-        if interaction == Interaction::Pressed {
-            state.set(GameState::LevelSelect);
+    for (interaction, button) in buttons.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
         }
-    }
-    for &interaction in retry.iter() {
-        if interaction == Interaction::Pressed {
-            // janky but it works
-            commands.spawn((ReenterLevel(level.level), LevelSelectWindow));
-            state.set(GameState::LevelSelect);
+        match button {
+            EndButton::NextLevel => {
+                if let Some(next) = campaigns.scene(scene.campaign, scene.level + 1) {
+                    *scene = next;
+                    running_state.set(GameRunning::Restarting);
+                }
+            }
+            EndButton::BackToMenu => state.set(GameState::LevelSelect),
+            EndButton::Retry => running_state.set(GameRunning::Restarting),
         }
     }
 }
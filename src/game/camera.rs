@@ -0,0 +1,240 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use super::hero::{Hero, HeroIndex};
+use crate::input::{ActionState, GameCursor, GamepadSticks, InputAction};
+use crate::sound::EAR_GAP;
+use crate::tile::grid_to_world;
+use crate::LevelScene;
+
+/// Free space around the level the camera can still show, in world units
+const LEVEL_MARGIN: f32 = 192.0;
+const MIN_SCALE: f32 = 0.3;
+/// How far you can zoom out, relative to the zoom that fits the whole level
+const MAX_SCALE_FACTOR: f32 = 1.5;
+/// Rate at which the camera eases towards where it wants to be, higher is snappier
+const SMOOTHING: f32 = 10.0;
+/// Screen pixels per second when panning with the right stick at full tilt
+const STICK_PAN_SPEED: f32 = 900.0;
+/// Scroll lines per second when holding a trigger all the way down
+const TRIGGER_ZOOM_SPEED: f32 = 10.0;
+
+/// Where the camera wants to be, the actual transform follows it smoothly
+#[derive(Component, Debug, Clone, Copy)]
+pub struct CameraController {
+    pub target_translation: Vec2,
+    pub target_scale: f32,
+    pub mode: CameraMode,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum CameraMode {
+    #[default]
+    Free,
+    /// Keeps the hero with this index in `LevelScene::heros` in the middle of the screen
+    Follow(usize),
+}
+
+/// Everything interesting in the level: heroes, their targets and the points of interest
+#[derive(Resource, Debug, Default, Clone, Copy, Deref)]
+pub struct LevelBounds(pub Rect);
+
+impl LevelBounds {
+    pub fn from_scene(scene: &LevelScene) -> Self {
+        let points = scene
+            .heros
+            .iter()
+            .flat_map(|hero| hero.targets.iter().copied().chain([hero.position]))
+            .chain(
+                scene
+                    .points_of_interest_map
+                    .keys()
+                    .map(|position| grid_to_world(*position)),
+            );
+        let rect = points.fold(None, |rect: Option<Rect>, point| {
+            Some(match rect {
+                Some(rect) => rect.union_point(point),
+                None => Rect::from_center_size(point, Vec2::ZERO),
+            })
+        });
+        Self(rect.unwrap_or_default())
+    }
+
+    /// The bounds with some room to spare, which is as far as the camera may pan
+    pub fn with_margin(&self) -> Rect {
+        self.inset(LEVEL_MARGIN)
+    }
+
+    /// Zoom at which the level and its margin just fit in a window of this size,
+    /// small levels keep the default zoom
+    pub fn fit_scale(&self, window_size: Vec2) -> f32 {
+        let size = self.with_margin().size() / window_size;
+        size.max_element().max(1.0)
+    }
+
+    pub fn max_scale(&self, window_size: Vec2) -> f32 {
+        self.fit_scale(window_size) * MAX_SCALE_FACTOR
+    }
+
+    pub fn clamp(&self, translation: Vec2) -> Vec2 {
+        let area = self.with_margin();
+        translation.clamp(area.min, area.max)
+    }
+}
+
+pub fn spawn_game_camera(
+    commands: &mut Commands,
+    bounds: &LevelBounds,
+    window_size: Vec2,
+) -> Entity {
+    let translation = bounds.center();
+    let scale = bounds.fit_scale(window_size);
+    commands
+        .spawn((
+            Camera2dBundle {
+                camera: Camera {
+                    clear_color: ClearColorConfig::Custom(Color::Rgba {
+                        red: 0.3,
+                        green: 1.0,
+                        blue: 1.0,
+                        alpha: 0.0,
+                    }),
+                    ..default()
+                },
+                transform: Transform {
+                    translation: translation.extend(999.9),
+                    scale: Vec3::new(scale, scale, 1.0),
+                    ..default()
+                },
+                ..default()
+            },
+            CameraController {
+                target_translation: translation,
+                target_scale: scale,
+                mode: CameraMode::Free,
+            },
+            // spell and hazard sounds are heard from where the camera is
+            SpatialListener::new(EAR_GAP),
+        ))
+        .id()
+}
+
+pub fn move_camera(
+    mut camera_query: Query<&mut CameraController>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    mut event_reader: EventReader<CursorMoved>,
+    interaction_query: Query<&Interaction>,
+    actions: Res<ActionState>,
+    cursor: Res<GameCursor>,
+    sticks: Res<GamepadSticks>,
+    time: Res<Time<Real>>,
+    bounds: Res<LevelBounds>,
+) {
+    let Ok(mut controller) = camera_query.get_single_mut() else {
+        return;
+    };
+    let window = window.single();
+    let window_size = Vec2::new(window.width(), window.height());
+    for event in event_reader.read() {
+        if !interaction_query
+            .iter()
+            .all(|interaction| *interaction == Interaction::None)
+        {
+            break;
+        }
+        if actions.pressed(InputAction::Pan) {
+            let zoom_factor = controller.target_scale;
+            let delta = event.delta.unwrap_or_default();
+            controller.target_translation += Vec2::new(-delta.x, delta.y) * zoom_factor;
+            // panning by hand lets go of the hero
+            controller.mode = CameraMode::Free;
+        }
+    }
+    if sticks.right != Vec2::ZERO {
+        let zoom_factor = controller.target_scale;
+        controller.target_translation +=
+            sticks.right * STICK_PAN_SPEED * zoom_factor * time.delta_seconds();
+        controller.mode = CameraMode::Free;
+    }
+
+    let zoom = actions.value(InputAction::ZoomIn) - actions.value(InputAction::ZoomOut)
+        + sticks.zoom * TRIGGER_ZOOM_SPEED * time.delta_seconds();
+    let mouse_on_game = cursor.from_gamepad
+        || interaction_query
+            .iter()
+            .all(|interaction| *interaction == Interaction::None);
+    if zoom != 0.0 && mouse_on_game {
+        const SCROLL_SPEED: f32 = 0.1;
+        let old_scale = controller.target_scale;
+        let new_scale = (old_scale - (zoom * SCROLL_SPEED).clamp(-1.0, 1.0))
+            .clamp(MIN_SCALE, bounds.max_scale(window_size));
+
+        // keep the point under the cursor in place, unless we're looking at a hero
+        if let (Some(cursor), CameraMode::Free) = (cursor.position, controller.mode) {
+            let from_center = (cursor - window_size / 2.0) * Vec2::new(1.0, -1.0);
+            controller.target_translation += from_center * (old_scale - new_scale);
+        }
+        controller.target_scale = new_scale;
+    }
+    controller.target_translation = bounds.clamp(controller.target_translation);
+}
+
+/// Follows the next hero, or the previous one while holding shift
+pub fn cycle_followed_hero(
+    actions: Res<ActionState>,
+    input: Res<ButtonInput<KeyCode>>,
+    mut camera_query: Query<&mut CameraController>,
+    heros: Query<&HeroIndex>,
+) {
+    if !actions.just_pressed(InputAction::NextHero) {
+        return;
+    }
+    let Ok(mut controller) = camera_query.get_single_mut() else {
+        return;
+    };
+    let hero_count = heros.iter().count();
+    if hero_count == 0 {
+        return;
+    }
+    let backwards = input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let next = match (controller.mode, backwards) {
+        (CameraMode::Free, false) => 0,
+        (CameraMode::Free, true) => hero_count - 1,
+        (CameraMode::Follow(index), false) => (index + 1) % hero_count,
+        (CameraMode::Follow(index), true) => (index + hero_count - 1) % hero_count,
+    };
+    controller.mode = CameraMode::Follow(next);
+}
+
+pub fn follow_hero(
+    mut camera_query: Query<&mut CameraController>,
+    heros: Query<(&Hero, &HeroIndex)>,
+    bounds: Res<LevelBounds>,
+) {
+    let Ok(mut controller) = camera_query.get_single_mut() else {
+        return;
+    };
+    let CameraMode::Follow(index) = controller.mode else {
+        return;
+    };
+    match heros.iter().find(|(_, hero_index)| hero_index.0 == index) {
+        Some((hero, _)) => controller.target_translation = bounds.clamp(hero.position),
+        None => controller.mode = CameraMode::Free,
+    }
+}
+
+/// Uses real time, so the camera keeps working while the game is paused or sped up
+pub fn smooth_camera(
+    time: Res<Time<Real>>,
+    mut camera_query: Query<(&mut Transform, &CameraController)>,
+) {
+    let t = 1.0 - (-SMOOTHING * time.delta_seconds()).exp();
+    for (mut transform, controller) in camera_query.iter_mut() {
+        let translation = transform
+            .translation
+            .xy()
+            .lerp(controller.target_translation, t);
+        transform.translation = translation.extend(transform.translation.z);
+        let scale = transform.scale.x + (controller.target_scale - transform.scale.x) * t;
+        transform.scale = Vec3::new(scale, scale, 1.0);
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::hero::Hero;
+use crate::sound::{PlaySound, SoundRegistry};
+use crate::EndGameEvent;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DamageKind {
+    #[default]
+    Fire,
+    Spike,
+    /// Falling into a hole, always lethal
+    Fall,
+}
+
+/// Fraction of the damage of each kind a hero ignores, 1.0 means immune
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Resistances {
+    pub fire: f32,
+    pub spike: f32,
+}
+
+impl Resistances {
+    pub fn damage_factor(&self, kind: DamageKind) -> f32 {
+        match kind {
+            DamageKind::Fire => 1.0 - self.fire,
+            DamageKind::Spike => 1.0 - self.spike,
+            DamageKind::Fall => 1.0,
+        }
+    }
+}
+
+/// Everything that hurts a hero sends one of these instead of touching the health bar
+#[derive(Event, Debug, Clone, Copy)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: f32,
+    pub kind: DamageKind,
+    /// The firewall or tile that did it, if it still has an entity
+    pub source: Option<Entity>,
+    /// Where the hero was when it got hit
+    pub position: Vec2,
+}
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct HealEvent {
+    pub target: Entity,
+    pub amount: f32,
+    pub source: Option<Entity>,
+}
+
+/// Sent once when a hero runs out of health, with the damage that finished them off
+#[derive(Event, Debug, Clone, Copy)]
+pub struct HeroDied {
+    pub cause: DamageEvent,
+}
+
+pub fn apply_health_events(
+    mut heros: Query<&mut Hero>,
+    mut damage_events: EventReader<DamageEvent>,
+    mut heal_events: EventReader<HealEvent>,
+    mut died_writer: EventWriter<HeroDied>,
+    mut end_writer: EventWriter<EndGameEvent>,
+) {
+    for heal in heal_events.read() {
+        let Ok(mut hero) = heros.get_mut(heal.target) else {
+            continue;
+        };
+        let health_bar = &mut hero.health_bar;
+        if health_bar.current_health > 0.0 {
+            health_bar.current_health =
+                (health_bar.current_health + heal.amount).min(health_bar.max_health);
+        }
+    }
+
+    for damage in damage_events.read() {
+        let Ok(mut hero) = heros.get_mut(damage.target) else {
+            continue;
+        };
+        // dead heros can't die again
+        if hero.health_bar.current_health <= 0.0 {
+            continue;
+        }
+        let amount = damage.amount * hero.resistances.damage_factor(damage.kind);
+        let health_bar = &mut hero.health_bar;
+        health_bar.current_health =
+            (health_bar.current_health - amount).clamp(0.0, health_bar.max_health);
+
+        if health_bar.current_health <= 0.0 {
+            died_writer.send(HeroDied { cause: *damage });
+            end_writer.send(EndGameEvent::Loss);
+        }
+    }
+}
+
+/// Fire hurts every frame, so its sound only starts again after this long
+const HAZARD_SOUND_INTERVAL: Duration = Duration::from_secs(1);
+
+pub fn play_hazard_sounds(
+    mut damage_reader: EventReader<DamageEvent>,
+    time: Res<Time>,
+    registry: Res<SoundRegistry>,
+    mut last_played: Local<HashMap<(Entity, DamageKind), Duration>>,
+    mut sound_writer: EventWriter<PlaySound>,
+) {
+    let now = time.elapsed();
+    for damage in damage_reader.read() {
+        let Some(sound) = registry.damage.get(&damage.kind) else {
+            continue;
+        };
+        let last = last_played.get(&(damage.target, damage.kind)).copied();
+        if last.is_some_and(|last| now.saturating_sub(last) < HAZARD_SOUND_INTERVAL) {
+            continue;
+        }
+        last_played.insert((damage.target, damage.kind), now);
+        sound_writer.send(PlaySound::at(sound.clone(), damage.position));
+    }
+}
@@ -6,13 +6,21 @@ use bevy::{prelude::*, sprite::Anchor};
 use rand::prelude::random;
 use serde::{Deserialize, Serialize};
 
+use super::damage::{DamageEvent, DamageKind, HealEvent, Resistances};
 use super::{AnimationTimer, FireWall, GameWindow, HealingCircle, WindGust};
-use crate::tile::{world_to_grid, Tile, grid_to_world};
+use crate::campaign::Campaigns;
+use crate::settings::GameSettings;
+use crate::sound::PlaySound;
+use crate::tile::{world_to_grid, Collision, TileEntities, Trigger, grid_to_world};
 use crate::{EndGameEvent, LevelScene};
 
 #[derive(Default, Debug, Clone, Component, Serialize, Deserialize)]
 pub struct Hero {
+    /// Can also be given on the level map, see `level_map::TARGET`
+    #[serde(default)]
     pub targets: Vec<Vec2>,
+    /// Can also be given on the level map, see `level_map::HERO`
+    #[serde(default)]
     pub position: Vec2,
     pub speed: f32,
     pub hero_type: HeroType,
@@ -20,10 +28,15 @@ pub struct Hero {
     pub current_target: usize,
     #[serde(flatten)]
     pub health_bar: HealthBar,
+    #[serde(default)]
+    pub resistances: Resistances,
     #[serde(skip)]
     pub rand: u8,
     #[serde(skip)]
     pub seen_poi: HashSet<IVec2>,
+    /// The grid position the hero was on last frame, to tell when it steps on a tile
+    #[serde(skip)]
+    pub current_tile: Option<IVec2>,
 }
 
 impl Hero {
@@ -46,16 +59,35 @@ pub enum HeroType {
     RerinGuard,
 }
 
+impl fmt::Display for HeroType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            HeroType::JohnHeron => "John Heron",
+            HeroType::RerinGuard => "Rerin Guard",
+        })
+    }
+}
+
 #[derive(Component)]
 pub struct HealthBarComponent;
 
+/// The part of the health bar that shrinks
+#[derive(Component)]
+pub struct HealthBarFill;
+
+/// Position of the hero in `LevelScene::heros`
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeroIndex(pub usize);
+
 pub fn create_hero(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut scene: ResMut<LevelScene>,
     mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    settings: Res<GameSettings>,
 ) {
-    for hero in scene.heros.iter_mut() {
+    let colors = settings.palette.colors();
+    for (index, hero) in scene.heros.iter_mut().enumerate() {
         // Texture
         let texture = asset_server.load(match hero.hero_type {
             HeroType::JohnHeron => "JohnHeron.png",
@@ -81,6 +113,7 @@ pub fn create_hero(
                     ..default()
                 },
                 hero.clone(),
+                HeroIndex(index),
                 timer,
                 GameWindow,
             ))
@@ -95,7 +128,7 @@ pub fn create_hero(
                                 ..default()
                             },
                             sprite: Sprite {
-                                color: Color::RED,
+                                color: colors.health_missing,
                                 custom_size: Some(Vec2::ONE),
                                 ..default()
                             },
@@ -107,7 +140,7 @@ pub fn create_hero(
                         parent.spawn((
                             SpriteBundle {
                                 sprite: Sprite {
-                                    color: Color::FUCHSIA,
+                                    color: colors.health,
                                     anchor: Anchor::CenterLeft,
                                     ..default()
                                 },
@@ -118,7 +151,7 @@ pub fn create_hero(
                                 },
                                 ..default()
                             },
-                            HealthBarComponent,
+                            HealthBarFill,
                         ));
                     });
             });
@@ -128,19 +161,26 @@ pub fn create_hero(
 pub fn move_heros(
     time: Res<Time>,
     mut query: Query<(
+        Entity,
         &mut Hero,
         &mut Transform,
         &mut TextureAtlas,
         &mut Sprite,
         &mut AnimationTimer,
     )>,
-    fires: Query<&FireWall>,
-    healing: Query<&HealingCircle>,
+    fires: Query<(Entity, &FireWall)>,
+    healing: Query<(Entity, &HealingCircle)>,
     winds: Query<&WindGust>,
     scene: Res<LevelScene>,
+    campaigns: Res<Campaigns>,
+    tile_entities: Res<TileEntities>,
     mut event_writer: EventWriter<EndGameEvent>,
+    mut damage_writer: EventWriter<DamageEvent>,
+    mut heal_writer: EventWriter<HealEvent>,
+    mut sound_writer: EventWriter<PlaySound>,
 ) {
-    for (mut hero, mut transform, mut atlas, mut sprite, mut timer) in query.iter_mut() {
+    let tiles = &campaigns[scene.campaign].tiles;
+    for (entity, mut hero, mut transform, mut atlas, mut sprite, mut timer) in query.iter_mut() {
         let direction = hero.target() - hero.position;
 
         // Animation
@@ -151,17 +191,23 @@ pub fn move_heros(
             atlas.index = if atlas.index == 3 { 0 } else { atlas.index + 1 }
         }
 
-        let clostest_fire = fires.iter().min_by(|x, y| {
+        let clostest_fire = fires.iter().min_by(|(_, x), (_, y)| {
             x.position
                 .distance(hero.position)
                 .total_cmp(&y.position.distance(hero.position))
         });
         // fire damage
-        if let Some(fire) = clostest_fire {
+        if let Some((fire_entity, fire)) = clostest_fire {
             let distance = fire.position - hero.position;
             let distance_len = distance.length();
             if distance_len <= 55.0 {
-                hero.health_bar.current_health -= (55.0 - distance.length()) * time.delta_seconds();
+                damage_writer.send(DamageEvent {
+                    target: entity,
+                    amount: (55.0 - distance.length()) * time.delta_seconds(),
+                    kind: DamageKind::Fire,
+                    source: Some(fire_entity),
+                    position: hero.position,
+                });
             }
         }
 
@@ -185,7 +231,7 @@ pub fn move_heros(
             None => (
                 false,
                 match clostest_fire {
-                    Some(fire) => {
+                    Some((_, fire)) => {
                         let distance = fire.position - hero.position;
                         let distance_len = distance.length();
 
@@ -224,31 +270,44 @@ pub fn move_heros(
         };
 
         // healing
-        for circle in healing.iter() {
+        for (circle_entity, circle) in healing.iter() {
             let distance = circle.position.distance(hero.position);
             if distance <= 60.0 {
                 // HP/s
                 const HEALING_AMOUNT: f32 = 8.0;
-                hero.health_bar.current_health = (hero.health_bar.current_health
-                    + HEALING_AMOUNT * time.delta_seconds())
-                .min(hero.health_bar.max_health);
+                heal_writer.send(HealEvent {
+                    target: entity,
+                    amount: HEALING_AMOUNT * time.delta_seconds(),
+                    source: Some(circle_entity),
+                });
             }
         }
 
         // POI
         let grid_pos = world_to_grid(hero.position);
-        if !hero.seen_poi.contains(&grid_pos) {
-            if let Some(tile) = scene.points_of_interest_map.get(&grid_pos) {
-                match *tile {
-                    Tile::Spike => hero.health_bar.current_health -= 40.0,
-                    Tile::Hole => hero.health_bar.current_health = -1.0,
-                    _ => (),
-                };
-                hero.seen_poi.insert(grid_pos);
+        let entered = hero.current_tile != Some(grid_pos);
+        hero.current_tile = Some(grid_pos);
+        let tile = scene
+            .points_of_interest_map
+            .get(&grid_pos)
+            .map(|tile| &tiles[*tile]);
+        if let Some(tile) = tile {
+            let first_time = hero.seen_poi.insert(grid_pos);
+            if entered && (first_time || tile.trigger == Trigger::Repeating) {
+                if let Some(sound) = &tile.sound {
+                    sound_writer.send(PlaySound::at(sound.clone(), grid_to_world(grid_pos)));
+                }
+                if tile.damage > 0.0 {
+                    damage_writer.send(DamageEvent {
+                        target: entity,
+                        amount: tile.damage,
+                        kind: tile.damage_kind,
+                        source: tile_entities.get(&grid_pos).copied(),
+                        position: hero.position,
+                    });
+                }
             }
-        }
-        if let Some(tile) = scene.points_of_interest_map.get(&grid_pos) {
-            if tile == &Tile::Pole {
+            if tile.collision == Collision::Solid {
                 new_direction = hero.position - grid_to_world(grid_pos);
                 if new_direction == Vec2::ZERO {
                     new_direction = Vec2::Y;
@@ -265,7 +324,8 @@ pub fn move_heros(
         };
 
         // Finish when close to target
-        if new_direction.length() < hero.speed * time.delta_seconds() {
+        let speed = hero.speed * tile.map_or(1.0, |tile| tile.speed);
+        if new_direction.length() < speed * time.delta_seconds() {
             hero.position = hero.target();
             hero.current_target += 1;
             if hero.current_target == hero.targets.len() {
@@ -273,28 +333,29 @@ pub fn move_heros(
             }
         } else {
             // Movement
-            let speed = hero.speed;
             hero.position += new_direction.normalize() * speed * time.delta_seconds();
         }
         transform.translation = hero.position.extend(1.0);
-
-        if hero.health_bar.current_health <= 0.0 {
-            event_writer.send(EndGameEvent::Loss);
-        }
     }
 }
 
+/// Also recolours the bars when the palette is changed from the pause menu
 pub fn update_health_bars(
-    query: Query<(&Children, &Parent), With<HealthBarComponent>>,
-    mut healthbars: Query<&mut Transform, With<HealthBarComponent>>,
+    mut query: Query<(&Children, &Parent, &mut Sprite), With<HealthBarComponent>>,
+    mut healthbars: Query<
+        (&mut Transform, &mut Sprite),
+        (With<HealthBarFill>, Without<HealthBarComponent>),
+    >,
     heros: Query<&Hero>,
+    settings: Res<GameSettings>,
 ) {
-    for (hp, hero) in query.iter() {
+    let colors = settings.palette.colors();
+    for (hp, hero, mut background) in query.iter_mut() {
         // idk if there is a better way to get the hero and hp at the same time
         let Some(&hp) = hp.iter().next() else {
             continue;
         };
-        let Ok(mut hp) = healthbars.get_mut(hp) else {
+        let Ok((mut hp, mut fill)) = healthbars.get_mut(hp) else {
             continue;
         };
         let Ok(hero) = heros.get(hero.get()) else {
@@ -302,6 +363,10 @@ pub fn update_health_bars(
         };
         let hp_ratio = hero.health_bar.current_health / hero.health_bar.max_health;
         hp.scale.x = hp_ratio.max(0.0);
+        if settings.is_changed() {
+            background.color = colors.health_missing;
+            fill.color = colors.health;
+        }
     }
 }
 
@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use super::damage::{DamageKind, HeroDied};
+use super::hero::Hero;
+use super::speed::GameSpeed;
+use super::GameWindow;
+use crate::locale::LocalizedText;
+use crate::palette::PaletteColors;
+use crate::tile::world_to_grid;
+
+/// How often the health of every hero gets written down
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+/// The timeline on the loss screen never gets wider than this
+const MAX_TIMELINE_BARS: usize = 60;
+
+/// What happened during the current attempt at a level
+#[derive(Resource, Debug)]
+pub struct AttemptHistory {
+    pub elapsed: Duration,
+    /// Health of every hero as a fraction of their max health, one sample per `SAMPLE_INTERVAL`
+    pub health: BTreeMap<Entity, Vec<f32>>,
+    /// Every speed the attempt ran at and when it was picked. Only stored for now, the game has
+    /// no replays that play an attempt back.
+    pub speed_changes: Vec<(Duration, GameSpeed)>,
+    sample_timer: Timer,
+}
+
+impl Default for AttemptHistory {
+    fn default() -> Self {
+        Self {
+            elapsed: Duration::ZERO,
+            health: BTreeMap::new(),
+            speed_changes: Vec::new(),
+            sample_timer: Timer::new(SAMPLE_INTERVAL, TimerMode::Repeating),
+        }
+    }
+}
+
+impl AttemptHistory {
+    fn sample(&mut self, heros: &Query<(Entity, &Hero)>) {
+        for (entity, hero) in heros.iter() {
+            let ratio = hero.health_bar.current_health / hero.health_bar.max_health;
+            self.health.entry(entity).or_default().push(ratio.max(0.0));
+        }
+    }
+}
+
+pub fn record_history(
+    time: Res<Time>,
+    mut history: ResMut<AttemptHistory>,
+    heros: Query<(Entity, &Hero)>,
+    mut died_reader: EventReader<HeroDied>,
+) {
+    if history.health.is_empty() {
+        history.sample(&heros);
+    }
+    history.elapsed += time.delta();
+    history.sample_timer.tick(time.delta());
+    // always write down the moment someone dies, so the timeline ends at zero
+    let someone_died = died_reader.read().count() > 0;
+    if history.sample_timer.just_finished() || someone_died {
+        history.sample(&heros);
+    }
+}
+
+/// "John Heron fell into a pit at (3,2) after 4.5 seconds"
+pub fn describe_death(hero: &Hero, death: &HeroDied, elapsed: Duration) -> LocalizedText {
+    let tile = world_to_grid(death.cause.position);
+    let key = match death.cause.kind {
+        DamageKind::Fire => "defeat.fire",
+        DamageKind::Spike => "defeat.spike",
+        DamageKind::Fall => "defeat.fall",
+    };
+    LocalizedText::key(key)
+        .with_arg("hero", hero.hero_type)
+        .with_arg("x", tile.x)
+        .with_arg("y", tile.y)
+        .with_arg("seconds", format!("{:.1}", elapsed.as_secs_f32()))
+}
+
+pub fn spawn_defeat_report(
+    commands: &mut Commands,
+    death: Option<&HeroDied>,
+    heros: &Query<&Hero>,
+    history: &AttemptHistory,
+    colors: &PaletteColors,
+) {
+    let dead_hero = death.and_then(|death| Some((death, heros.get(death.cause.target).ok()?)));
+    let message = match dead_hero {
+        Some((death, hero)) => describe_death(hero, death, history.elapsed),
+        None => LocalizedText::key("defeat.unknown")
+            .with_arg("seconds", format!("{:.1}", history.elapsed.as_secs_f32())),
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Percent(5.0),
+                    justify_self: JustifySelf::Center,
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            },
+            GameWindow,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    String::new(),
+                    TextStyle {
+                        font_size: 40.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                )
+                .with_text_justify(JustifyText::Center),
+                message,
+            ));
+
+            for (&entity, samples) in history.health.iter() {
+                let Ok(hero) = heros.get(entity) else {
+                    continue;
+                };
+                let died = death.is_some_and(|death| death.cause.target == entity);
+                spawn_timeline_row(parent, hero, samples, died, colors);
+            }
+        });
+}
+
+/// One bar per chunk of samples, showing the lowest health in that chunk
+fn spawn_timeline_row(
+    parent: &mut ChildBuilder,
+    hero: &Hero,
+    samples: &[f32],
+    died: bool,
+    colors: &PaletteColors,
+) {
+    let chunk_size = samples.len().div_ceil(MAX_TIMELINE_BARS).max(1);
+    let bar_color = if died { colors.died } else { colors.survived };
+    // the name says who died as well, so it isn't only told apart by the colour
+    let name = if died {
+        LocalizedText::key("defeat.died").with_arg("hero", hero.hero_type)
+    } else {
+        hero.hero_type.to_string().into()
+    };
+
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                align_items: AlignItems::End,
+                column_gap: Val::Px(10.0),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    String::new(),
+                    TextStyle {
+                        font_size: 25.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ),
+                name,
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        height: Val::Px(60.0),
+                        align_items: AlignItems::End,
+                        column_gap: Val::Px(1.0),
+                        ..default()
+                    },
+                    background_color: Color::rgba(0.0, 0.0, 0.0, 0.4).into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for chunk in samples.chunks(chunk_size) {
+                        let lowest = chunk.iter().copied().fold(1.0, f32::min);
+                        parent.spawn(NodeBundle {
+                            style: Style {
+                                width: Val::Px(6.0),
+                                height: Val::Percent(lowest * 100.0),
+                                ..default()
+                            },
+                            background_color: bar_color.into(),
+                            ..default()
+                        });
+                    }
+                });
+        });
+}
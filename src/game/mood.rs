@@ -0,0 +1,37 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use super::damage::{DamageEvent, DamageKind};
+use super::hero::Hero;
+use crate::music::MusicMood;
+
+/// Below this part of their health a hero is in danger
+const DANGER_HEALTH: f32 = 0.35;
+/// How long things have to stay quiet before the music calms down again
+const CALM_DOWN: Duration = Duration::from_secs(3);
+
+/// The music gets intense while a hero is low on health or standing in fire
+pub fn update_music_mood(
+    heros: Query<&Hero>,
+    mut damage_reader: EventReader<DamageEvent>,
+    time: Res<Time>,
+    mut calm_for: Local<Duration>,
+    mut mood: ResMut<MusicMood>,
+) {
+    let burning = damage_reader
+        .read()
+        .any(|event| event.kind == DamageKind::Fire);
+    let hurt = heros
+        .iter()
+        .any(|hero| hero.health_bar.current_health < hero.health_bar.max_health * DANGER_HEALTH);
+    if burning || hurt {
+        *calm_for = Duration::ZERO;
+        mood.set_if_neq(MusicMood::Intense);
+    } else {
+        *calm_for += time.delta();
+        if *calm_for >= CALM_DOWN {
+            mood.set_if_neq(MusicMood::Calm);
+        }
+    }
+}
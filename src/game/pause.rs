@@ -0,0 +1,137 @@
+use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+use bevy::window::WindowFocused;
+
+use super::{GameRunning, GameWindow};
+use crate::focus::FocusScope;
+use crate::input::{ActionState, InputAction};
+use crate::locale::LocalizedText;
+use crate::settings::{GameSettings, SettingsState};
+use crate::widget::{ButtonColors, SpawnButton};
+use crate::GameState;
+
+/// Annotate everything in the pause overlay with this component
+#[derive(Component)]
+pub struct PauseMenu;
+
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub enum PauseButton {
+    Resume,
+    Restart,
+    Settings,
+    Quit,
+}
+
+pub fn toggle_pause(
+    actions: Res<ActionState>,
+    running_state: Res<State<GameRunning>>,
+    mut next_state: ResMut<NextState<GameRunning>>,
+) {
+    if !actions.just_pressed(InputAction::Pause) {
+        return;
+    }
+    match running_state.get() {
+        GameRunning::Running => next_state.set(GameRunning::Paused),
+        GameRunning::Paused => next_state.set(GameRunning::Running),
+        _ => {}
+    }
+}
+
+pub fn pause_on_focus_loss(
+    mut focus_events: EventReader<WindowFocused>,
+    settings: Res<GameSettings>,
+    mut next_state: ResMut<NextState<GameRunning>>,
+) {
+    let lost_focus = focus_events.read().any(|event| !event.focused);
+    if lost_focus && settings.pause_on_focus_loss {
+        next_state.set(GameRunning::Paused);
+    }
+}
+
+/// Everything that ticks with `Time` stops while the virtual clock is paused
+pub fn freeze_time(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
+
+pub fn unfreeze_time(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+pub fn spawn_pause_menu(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(30.0),
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+                // don't let clicks fall through to the spell buttons
+                focus_policy: FocusPolicy::Block,
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            PauseMenu,
+            GameWindow,
+            FocusScope(10),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    String::new(),
+                    TextStyle {
+                        font_size: 100.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ),
+                LocalizedText::key("pause.title"),
+            ));
+            for (button, key) in [
+                (PauseButton::Resume, "pause.resume"),
+                (PauseButton::Restart, "pause.restart"),
+                (PauseButton::Settings, "common.settings"),
+                (PauseButton::Quit, "pause.quit"),
+            ] {
+                parent
+                    .spawn_button(
+                        LocalizedText::key(key),
+                        60.0,
+                        ButtonColors::GAME,
+                        Style {
+                            min_width: Val::Percent(30.0),
+                            ..default()
+                        },
+                    )
+                    .insert(button);
+            }
+        });
+}
+
+pub fn pause_menu_pressed(
+    query: Query<(&Interaction, &PauseButton)>,
+    mut running_state: ResMut<NextState<GameRunning>>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut settings_state: ResMut<NextState<SettingsState>>,
+) {
+    for (interaction, button) in query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            PauseButton::Resume => running_state.set(GameRunning::Running),
+            PauseButton::Restart => running_state.set(GameRunning::Restarting),
+            PauseButton::Settings => settings_state.set(SettingsState::Open),
+            PauseButton::Quit => {
+                running_state.set(GameRunning::Running);
+                game_state.set(GameState::LevelSelect);
+            }
+        }
+    }
+}
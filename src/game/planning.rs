@@ -0,0 +1,143 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use super::{spell_sprite, CastSpell, GameRunning, GameWindow};
+use crate::input::{ActionState, InputAction, InputBindings};
+use crate::locale::{Locale, LocalizedText};
+
+/// Time between two planned spells when they are released one after another
+const RELEASE_STAGGER: Duration = Duration::from_millis(500);
+
+/// A spell placed while planning, shown as a see-through preview until it gets cast
+#[derive(Component, Debug)]
+pub struct PlannedSpell {
+    pub cast: CastSpell,
+    /// Counts up with every spell placed, entities don't keep that order when they get reused
+    pub seq: u32,
+    /// `None` until the player releases the plan
+    pub release: Option<Timer>,
+}
+
+#[derive(Component)]
+pub struct PlanningHint;
+
+pub fn plan_spell(
+    commands: &mut Commands,
+    cast: CastSpell,
+    seq: u32,
+    asset_server: &AssetServer,
+    texture_atlas_layouts: &mut Assets<TextureAtlasLayout>,
+) {
+    let mut sprite = spell_sprite(&cast, asset_server, texture_atlas_layouts);
+    sprite.sprite.color = Color::rgba(1.0, 1.0, 1.0, 0.5);
+    commands.spawn((
+        sprite,
+        PlannedSpell {
+            cast,
+            seq,
+            release: None,
+        },
+        GameWindow,
+    ));
+}
+
+/// Freezes the game for planning, and releases every planned spell at once.
+/// They can also be released one after another in the order they were placed.
+pub fn toggle_planning(
+    actions: Res<ActionState>,
+    running_state: Res<State<GameRunning>>,
+    mut next_state: ResMut<NextState<GameRunning>>,
+    mut planned_spells: Query<&mut PlannedSpell>,
+) {
+    match running_state.get() {
+        GameRunning::Running if actions.just_pressed(InputAction::Plan) => {
+            next_state.set(GameRunning::Planning);
+        }
+        GameRunning::Planning => {
+            let stagger = if actions.just_pressed(InputAction::Plan) {
+                Duration::ZERO
+            } else if actions.just_pressed(InputAction::CastPlanInOrder) {
+                RELEASE_STAGGER
+            } else {
+                return;
+            };
+            let mut unreleased: Vec<_> = planned_spells
+                .iter_mut()
+                .filter(|planned| planned.release.is_none())
+                .collect();
+            unreleased.sort_by_key(|planned| planned.seq);
+            for (i, planned) in unreleased.iter_mut().enumerate() {
+                planned.release = Some(Timer::new(stagger * i as u32, TimerMode::Once));
+            }
+            next_state.set(GameRunning::Running);
+        }
+        _ => {}
+    }
+}
+
+/// Takes back the last spell that hasn't been released yet
+pub fn undo_planned_spell(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    planned_spells: Query<(Entity, &PlannedSpell)>,
+) {
+    if !actions.just_pressed(InputAction::UndoPlannedSpell) {
+        return;
+    }
+    let last = planned_spells
+        .iter()
+        .filter(|(_, planned)| planned.release.is_none())
+        .max_by_key(|(_, planned)| planned.seq);
+    if let Some((entity, _)) = last {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn cast_planned_spells(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut planned_spells: Query<(Entity, &mut PlannedSpell)>,
+    mut cast_writer: EventWriter<CastSpell>,
+) {
+    for (entity, mut planned) in planned_spells.iter_mut() {
+        let Some(release) = planned.release.as_mut() else {
+            continue;
+        };
+        release.tick(time.delta());
+        if release.finished() {
+            cast_writer.send(planned.cast);
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+pub fn spawn_planning_hint(
+    mut commands: Commands,
+    bindings: Res<InputBindings>,
+    locale: Res<Locale>,
+) {
+    let describe = |action| bindings.describe(action, &locale);
+    commands.spawn((
+        TextBundle::from_section(
+            String::new(),
+            TextStyle {
+                font_size: 30.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.0),
+            left: Val::Px(10.0),
+            ..default()
+        }),
+        LocalizedText::key("game.planning_hint")
+            .with_arg("place", describe(InputAction::Cast))
+            .with_arg("cast", describe(InputAction::Plan))
+            .with_arg("in_order", describe(InputAction::CastPlanInOrder)),
+        PlanningHint,
+        GameWindow,
+    ));
+}
@@ -0,0 +1,149 @@
+use bevy::prelude::*;
+
+use super::history::AttemptHistory;
+use super::{highlight, GameWindow};
+use crate::input::{ActionState, InputAction};
+use crate::settings::GameSettings;
+
+/// How fast the simulation runs, applied to the virtual clock so every gameplay timer follows it
+#[derive(Component, Resource, Default, Debug, Clone, Copy, PartialEq)]
+pub enum GameSpeed {
+    Half,
+    #[default]
+    Normal,
+    Double,
+    Quadruple,
+}
+
+impl GameSpeed {
+    pub const ALL: [GameSpeed; 4] = [
+        GameSpeed::Half,
+        GameSpeed::Normal,
+        GameSpeed::Double,
+        GameSpeed::Quadruple,
+    ];
+
+    pub fn factor(self) -> f32 {
+        match self {
+            GameSpeed::Half => 0.5,
+            GameSpeed::Normal => 1.0,
+            GameSpeed::Double => 2.0,
+            GameSpeed::Quadruple => 4.0,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            GameSpeed::Half => "0.5x",
+            GameSpeed::Normal => "1x",
+            GameSpeed::Double => "2x",
+            GameSpeed::Quadruple => "4x",
+        }
+    }
+
+    fn index(self) -> usize {
+        Self::ALL.iter().position(|&speed| speed == self).unwrap()
+    }
+
+    pub fn faster(self) -> Self {
+        Self::ALL[(self.index() + 1).min(Self::ALL.len() - 1)]
+    }
+
+    pub fn slower(self) -> Self {
+        Self::ALL[self.index().saturating_sub(1)]
+    }
+}
+
+pub fn spawn_speed_buttons(commands: &mut Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    top: Val::Px(10.0),
+                    right: Val::Px(10.0),
+                    position_type: PositionType::Absolute,
+                    display: Display::Flex,
+                    ..default()
+                },
+                ..default()
+            },
+            GameWindow,
+        ))
+        .with_children(|parent| {
+            for speed in GameSpeed::ALL {
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                min_width: Val::Px(70.0),
+                                justify_content: JustifyContent::Center,
+                                border: UiRect::all(Val::Px(3.0)),
+                                margin: UiRect::all(Val::Px(3.0)),
+                                ..default()
+                            },
+                            background_color: Color::FUCHSIA.into(),
+                            ..default()
+                        },
+                        speed,
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(
+                            speed.label(),
+                            TextStyle {
+                                font_size: 30.0,
+                                color: Color::WHITE,
+                                ..default()
+                            },
+                        ));
+                    });
+            }
+        });
+}
+
+pub fn select_speed_button(
+    query: Query<(&Interaction, &GameSpeed)>,
+    mut game_speed: ResMut<GameSpeed>,
+) {
+    for (interaction, &speed) in query.iter() {
+        if *interaction == Interaction::Pressed {
+            *game_speed = speed;
+        }
+    }
+}
+
+pub fn select_speed_keybind(actions: Res<ActionState>, mut game_speed: ResMut<GameSpeed>) {
+    if actions.just_pressed(InputAction::SpeedUp) {
+        *game_speed = game_speed.faster();
+    } else if actions.just_pressed(InputAction::SlowDown) {
+        *game_speed = game_speed.slower();
+    }
+}
+
+/// Also runs on entering a level, so the speed of every attempt gets written down from the start
+pub fn apply_game_speed(
+    game_speed: Res<GameSpeed>,
+    mut time: ResMut<Time<Virtual>>,
+    mut history: ResMut<AttemptHistory>,
+) {
+    time.set_relative_speed(game_speed.factor());
+    let elapsed = history.elapsed;
+    history.speed_changes.push((elapsed, *game_speed));
+}
+
+/// Menus and the level select run at normal speed, whatever the level was set to
+pub fn reset_game_speed(mut time: ResMut<Time<Virtual>>) {
+    time.set_relative_speed(1.0);
+}
+
+pub fn highlight_selected_speed(
+    mut commands: Commands,
+    game_speed: Res<GameSpeed>,
+    settings: Res<GameSettings>,
+    mut buttons: Query<(Entity, &GameSpeed, &mut BorderColor)>,
+) {
+    let colors = settings.palette.colors();
+    for (entity, speed, mut border) in buttons.iter_mut() {
+        let selected = speed == game_speed.as_ref();
+        highlight(&mut commands, entity, &mut border, selected, colors);
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::sprite::MaterialMesh2dBundle;
+
+use super::GameWindow;
+use crate::campaign::Campaigns;
+use crate::tile::{grid_to_world, make_tile, TileEntities};
+use crate::LevelScene;
+
+/// Width and height in tiles of the squares the static tiles are batched in, so the camera
+/// can leave out the ones that are off screen
+const CHUNK_SIZE: i32 = 16;
+/// Tiles are drawn one grid cell wide
+const TILE_SIZE: f32 = 64.0;
+
+/// Tiles that do something when stepped on get an entity of their own, so damage can be traced
+/// back to them. The others are drawn as one mesh per chunk and sprite.
+pub fn spawn_tiles(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    scene: Res<LevelScene>,
+    campaigns: Res<Campaigns>,
+    mut tile_entities: ResMut<TileEntities>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let campaign = &campaigns[scene.campaign];
+    tile_entities.clear();
+
+    let mut chunks: HashMap<(IVec2, &str), Vec<IVec2>> = HashMap::new();
+    for (position, tile) in scene.points_of_interest_map.iter() {
+        let kind = &campaign.tiles[*tile];
+        if kind.has_behaviour() {
+            let entity = make_tile(*tile, *position, &mut commands, &asset_server, campaign);
+            tile_entities.insert(*position, entity);
+        } else if let Some(sprite) = &kind.sprite {
+            let chunk = position.div_euclid(IVec2::splat(CHUNK_SIZE));
+            chunks
+                .entry((chunk, sprite.as_str()))
+                .or_default()
+                .push(*position);
+        }
+    }
+
+    let mut sprite_materials = HashMap::new();
+    for ((_, sprite), positions) in chunks {
+        let material = sprite_materials
+            .entry(sprite)
+            .or_insert_with(|| {
+                let texture: Handle<Image> = asset_server.load(campaign.asset_path(sprite));
+                materials.add(ColorMaterial::from(texture))
+            })
+            .clone();
+        commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: meshes.add(chunk_mesh(&positions)).into(),
+                material,
+                transform: Transform::from_xyz(0.0, 0.0, 0.2),
+                ..default()
+            },
+            GameWindow,
+        ));
+    }
+}
+
+/// A quad with the whole texture on it for every position
+fn chunk_mesh(positions: &[IVec2]) -> Mesh {
+    let corners = [
+        (Vec2::new(-0.5, -0.5), [0.0, 1.0]),
+        (Vec2::new(0.5, -0.5), [1.0, 1.0]),
+        (Vec2::new(0.5, 0.5), [1.0, 0.0]),
+        (Vec2::new(-0.5, 0.5), [0.0, 0.0]),
+    ];
+    let mut vertices = Vec::with_capacity(positions.len() * 4);
+    let mut uvs = Vec::with_capacity(positions.len() * 4);
+    let mut indices = Vec::with_capacity(positions.len() * 6);
+    for position in positions {
+        let center = grid_to_world(*position);
+        let first = vertices.len() as u32;
+        for (corner, uv) in corners {
+            vertices.push((center + corner * TILE_SIZE).extend(0.0).to_array());
+            uvs.push(uv);
+        }
+        indices.extend([0, 1, 2, 0, 2, 3].map(|index| first + index));
+    }
+    let normals = vec![[0.0, 0.0, 1.0]; vertices.len()];
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, vertices)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+    .with_inserted_indices(Indices::U32(indices))
+}
@@ -1,6 +1,9 @@
 use bevy::prelude::*;
 
-use crate::{despawn_screen, GameState, BGM};
+use crate::locale::LocalizedText;
+use crate::music::PlayMusic;
+use crate::widget::{ButtonColors, SpawnButton};
+use crate::{despawn_screen, GameState};
 
 pub struct InfoPlugin;
 
@@ -21,11 +24,7 @@ pub struct InfoWindow;
 #[derive(Component)]
 struct BackToMainButton;
 
-fn setup(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut bgm_query: Query<(&mut BGM, Entity)>,
-) {
+fn setup(mut commands: Commands, mut music: EventWriter<PlayMusic>) {
     commands.spawn((
         Camera2dBundle {
             camera: Camera {
@@ -37,48 +36,25 @@ fn setup(
         InfoWindow,
     ));
 
-    if let Ok((mut bgm, entity)) = bgm_query.get_single_mut() {
-        if bgm.0 != "music/Main_menu.ogg" {
-            commands
-                .entity(entity)
-                .remove::<AudioSink>()
-                .insert(asset_server.load::<AudioSource>("music/Main_menu.ogg"));
-            bgm.0 = "music/Main_menu.ogg".to_string();
-        }
-    }
+    music.send(PlayMusic::looping("music/Main_menu.ogg"));
 
     commands
-        .spawn((
-            ButtonBundle {
-                style: Style {
-                    position_type: PositionType::Absolute,
-                    top: Val::Px(5.0),
-                    left: Val::Px(5.0),
-                    ..default()
-                },
-                background_color: Color::PINK.into(),
+        .spawn_button(
+            LocalizedText::key("common.back"),
+            50.0,
+            ButtonColors::MENU,
+            Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(5.0),
+                left: Val::Px(5.0),
                 ..default()
             },
-            BackToMainButton,
-            InfoWindow,
-        ))
-        .with_children(|parent| {
-            parent.spawn(
-                TextBundle::from_section(
-                    "Back",
-                    TextStyle {
-                        font_size: 50.0,
-                        color: Color::WHITE,
-                        ..default()
-                    },
-                )
-                .with_no_wrap(),
-            );
-        });
+        )
+        .insert((BackToMainButton, InfoWindow));
 
     commands.spawn((
         TextBundle::from_section(
-            include_str!("info.txt"),
+            String::new(),
             TextStyle {
                 font_size: 30.0,
                 color: Color::WHITE,
@@ -90,6 +66,7 @@ fn setup(
             margin: UiRect::all(Val::Percent(5.0)),
             ..default()
         }),
+        LocalizedText::key("info.text"),
         InfoWindow,
     ));
 }
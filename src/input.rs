@@ -0,0 +1,520 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::input::mouse::MouseWheel;
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+use bevy::window::PrimaryWindow;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::locale::{Locale, LocalizedText};
+
+pub struct InputPlugin;
+
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(InputBindings::load())
+            .init_resource::<ActionState>()
+            .init_resource::<GamepadSticks>()
+            .init_resource::<GameCursor>()
+            .add_systems(Startup, spawn_virtual_cursor)
+            .add_systems(
+                PreUpdate,
+                (
+                    update_action_state,
+                    update_gamepad_sticks,
+                    update_game_cursor,
+                )
+                    .chain()
+                    .after(InputSystem),
+            )
+            .add_systems(Update, draw_virtual_cursor);
+    }
+}
+
+/// Everything the player can do, systems ask `ActionState` about these instead of about keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InputAction {
+    /// Select the nth spell in the spell bar, starting at 1
+    SelectSpell(u8),
+    Cast,
+    Pan,
+    ZoomIn,
+    ZoomOut,
+    Pause,
+    /// Freeze the game to plan spells, or cast all of them at once
+    Plan,
+    CastPlanInOrder,
+    UndoPlannedSpell,
+    /// Hold shift to go to the previous hero instead
+    NextHero,
+    SpeedUp,
+    SlowDown,
+    NextSpell,
+    PreviousSpell,
+    // the menu actions move and press the focus, which doesn't happen while a level is played
+    MenuUp,
+    MenuDown,
+    MenuLeft,
+    MenuRight,
+    /// Hold shift to go to the previous button instead
+    MenuNext,
+    /// Press the focused menu button
+    MenuConfirm,
+}
+
+impl InputAction {
+    /// The name shown in the settings
+    pub fn text(self) -> LocalizedText {
+        let key = match self {
+            InputAction::SelectSpell(n) => {
+                return LocalizedText::key("action.select_spell").with_arg("number", n)
+            }
+            InputAction::Cast => "action.cast",
+            InputAction::Pan => "action.pan",
+            InputAction::ZoomIn => "action.zoom_in",
+            InputAction::ZoomOut => "action.zoom_out",
+            InputAction::Pause => "action.pause",
+            InputAction::Plan => "action.plan",
+            InputAction::CastPlanInOrder => "action.cast_plan_in_order",
+            InputAction::UndoPlannedSpell => "action.undo_planned_spell",
+            InputAction::NextHero => "action.next_hero",
+            InputAction::SpeedUp => "action.speed_up",
+            InputAction::SlowDown => "action.slow_down",
+            InputAction::NextSpell => "action.next_spell",
+            InputAction::PreviousSpell => "action.previous_spell",
+            InputAction::MenuUp => "action.menu_up",
+            InputAction::MenuDown => "action.menu_down",
+            InputAction::MenuLeft => "action.menu_left",
+            InputAction::MenuRight => "action.menu_right",
+            InputAction::MenuNext => "action.menu_next",
+            InputAction::MenuConfirm => "action.menu_confirm",
+        };
+        LocalizedText::key(key)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    WheelUp,
+    WheelDown,
+    /// A button on any connected gamepad
+    Gamepad(GamepadButtonType),
+}
+
+impl Binding {
+    /// Keys go by their name on an English keyboard, which is what is printed on most of them
+    pub fn text(self) -> LocalizedText {
+        match self {
+            Binding::Key(key) => format!("{key:?}").into(),
+            Binding::Mouse(MouseButton::Left) => LocalizedText::key("binding.left_click"),
+            Binding::Mouse(MouseButton::Right) => LocalizedText::key("binding.right_click"),
+            Binding::Mouse(MouseButton::Middle) => LocalizedText::key("binding.middle_click"),
+            Binding::Mouse(button) => {
+                LocalizedText::key("binding.mouse").with_arg("button", format!("{button:?}"))
+            }
+            Binding::WheelUp => LocalizedText::key("binding.scroll_up"),
+            Binding::WheelDown => LocalizedText::key("binding.scroll_down"),
+            Binding::Gamepad(button) => {
+                LocalizedText::key("binding.gamepad").with_arg("button", format!("{button:?}"))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActionBindings {
+    pub action: InputAction,
+    pub inputs: Vec<Binding>,
+}
+
+/// Which inputs trigger which action, saved to `config/bindings.toml`
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputBindings {
+    pub bindings: Vec<ActionBindings>,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        use Binding::*;
+        use GamepadButtonType as Pad;
+        use InputAction::*;
+        let bindings = [
+            (
+                SelectSpell(1),
+                vec![Key(KeyCode::Digit1), Key(KeyCode::Numpad1)],
+            ),
+            (
+                SelectSpell(2),
+                vec![Key(KeyCode::Digit2), Key(KeyCode::Numpad2)],
+            ),
+            (
+                SelectSpell(3),
+                vec![Key(KeyCode::Digit3), Key(KeyCode::Numpad3)],
+            ),
+            (Cast, vec![Mouse(MouseButton::Left), Gamepad(Pad::South)]),
+            (Pan, vec![Mouse(MouseButton::Right)]),
+            (ZoomIn, vec![WheelUp]),
+            (ZoomOut, vec![WheelDown]),
+            (Pause, vec![Key(KeyCode::Escape), Gamepad(Pad::Start)]),
+            (Plan, vec![Key(KeyCode::Space), Gamepad(Pad::Select)]),
+            (
+                CastPlanInOrder,
+                vec![Key(KeyCode::Enter), Gamepad(Pad::RightThumb)],
+            ),
+            (
+                UndoPlannedSpell,
+                vec![Key(KeyCode::Backspace), Gamepad(Pad::LeftThumb)],
+            ),
+            (NextHero, vec![Key(KeyCode::Tab), Gamepad(Pad::North)]),
+            (
+                SpeedUp,
+                vec![
+                    Key(KeyCode::Equal),
+                    Key(KeyCode::NumpadAdd),
+                    Gamepad(Pad::RightTrigger),
+                ],
+            ),
+            (
+                SlowDown,
+                vec![
+                    Key(KeyCode::Minus),
+                    Key(KeyCode::NumpadSubtract),
+                    Gamepad(Pad::LeftTrigger),
+                ],
+            ),
+            (NextSpell, vec![Gamepad(Pad::East)]),
+            (PreviousSpell, vec![Gamepad(Pad::West)]),
+            (MenuUp, vec![Key(KeyCode::ArrowUp), Gamepad(Pad::DPadUp)]),
+            (
+                MenuDown,
+                vec![Key(KeyCode::ArrowDown), Gamepad(Pad::DPadDown)],
+            ),
+            (
+                MenuLeft,
+                vec![Key(KeyCode::ArrowLeft), Gamepad(Pad::DPadLeft)],
+            ),
+            (
+                MenuRight,
+                vec![Key(KeyCode::ArrowRight), Gamepad(Pad::DPadRight)],
+            ),
+            (MenuNext, vec![Key(KeyCode::Tab)]),
+            (
+                MenuConfirm,
+                vec![
+                    Key(KeyCode::Enter),
+                    Key(KeyCode::NumpadEnter),
+                    Gamepad(Pad::South),
+                ],
+            ),
+        ];
+        Self {
+            bindings: bindings
+                .into_iter()
+                .map(|(action, inputs)| ActionBindings { action, inputs })
+                .collect(),
+        }
+    }
+}
+
+impl InputBindings {
+    pub const FILE_NAME: &'static str = "bindings.toml";
+
+    /// The saved bindings, with the default inputs for actions added since the file was saved
+    pub fn load() -> Self {
+        let mut bindings: Self = config::load(Self::FILE_NAME);
+        let missing: Vec<_> = Self::default()
+            .bindings
+            .into_iter()
+            .filter(|default| {
+                !bindings
+                    .bindings
+                    .iter()
+                    .any(|saved| saved.action == default.action)
+            })
+            .collect();
+        if !missing.is_empty() {
+            bindings.bindings.extend(missing);
+            config::save(Self::FILE_NAME, &bindings);
+        }
+        bindings
+    }
+
+    pub fn inputs(&self, action: InputAction) -> &[Binding] {
+        self.bindings
+            .iter()
+            .find(|bindings| bindings.action == action)
+            .map_or(&[], |bindings| &bindings.inputs)
+    }
+
+    /// "Digit1 / Numpad1", for showing the controls to the player
+    pub fn describe(&self, action: InputAction, locale: &Locale) -> String {
+        let inputs: Vec<_> = self
+            .inputs(action)
+            .iter()
+            .map(|input| locale.text(&input.text()))
+            .collect();
+        if inputs.is_empty() {
+            locale.get("binding.unbound").to_string()
+        } else {
+            inputs.join(" / ")
+        }
+    }
+
+    /// Other actions that are triggered by one of the inputs of this action
+    pub fn conflicts(&self, action: InputAction) -> Vec<InputAction> {
+        let inputs = self.inputs(action);
+        self.bindings
+            .iter()
+            .filter(|other| other.action != action)
+            .filter(|other| other.inputs.iter().any(|input| inputs.contains(input)))
+            .map(|other| other.action)
+            .collect()
+    }
+}
+
+/// What the player is doing this frame, in terms of `InputAction`s
+#[derive(Resource, Debug, Default)]
+pub struct ActionState {
+    pressed: HashSet<InputAction>,
+    just_pressed: HashSet<InputAction>,
+    just_released: HashSet<InputAction>,
+    values: HashMap<InputAction, f32>,
+}
+
+impl ActionState {
+    pub fn pressed(&self, action: InputAction) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: InputAction) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    pub fn just_released(&self, action: InputAction) -> bool {
+        self.just_released.contains(&action)
+    }
+
+    /// How much of the action happened this frame, the scroll distance for the wheel
+    /// or 1.0 for a button that was just pressed
+    pub fn value(&self, action: InputAction) -> f32 {
+        self.values.get(&action).copied().unwrap_or_default()
+    }
+}
+
+/// Scroll distance this frame, split into up and down
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Wheel {
+    pub up: f32,
+    pub down: f32,
+}
+
+impl Wheel {
+    pub fn read(events: &mut EventReader<MouseWheel>) -> Self {
+        events.read().fold(Wheel::default(), |wheel, event| {
+            let amount = event.x + event.y;
+            Wheel {
+                up: wheel.up + amount.max(0.0),
+                down: wheel.down + (-amount).max(0.0),
+            }
+        })
+    }
+}
+
+fn update_action_state(
+    bindings: Res<InputBindings>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    mut wheel_events: EventReader<MouseWheel>,
+    mut state: ResMut<ActionState>,
+) {
+    let wheel = Wheel::read(&mut wheel_events);
+    let any_gamepad =
+        |button_type, check: fn(&ButtonInput<GamepadButton>, GamepadButton) -> bool| {
+            gamepads
+                .iter()
+                .any(|gamepad| check(&gamepad_buttons, GamepadButton::new(gamepad, button_type)))
+        };
+    let pressed = |binding: &Binding| match *binding {
+        Binding::Key(key) => keys.pressed(key),
+        Binding::Mouse(button) => mouse.pressed(button),
+        Binding::WheelUp => wheel.up > 0.0,
+        Binding::WheelDown => wheel.down > 0.0,
+        Binding::Gamepad(button) => any_gamepad(button, ButtonInput::pressed),
+    };
+    let just_pressed = |binding: &Binding| match *binding {
+        Binding::Key(key) => keys.just_pressed(key),
+        Binding::Mouse(button) => mouse.just_pressed(button),
+        Binding::WheelUp => wheel.up > 0.0,
+        Binding::WheelDown => wheel.down > 0.0,
+        Binding::Gamepad(button) => any_gamepad(button, ButtonInput::just_pressed),
+    };
+    let just_released = |binding: &Binding| match *binding {
+        Binding::Key(key) => keys.just_released(key),
+        Binding::Mouse(button) => mouse.just_released(button),
+        Binding::WheelUp | Binding::WheelDown => false,
+        Binding::Gamepad(button) => any_gamepad(button, ButtonInput::just_released),
+    };
+    let value = |binding: &Binding| match *binding {
+        Binding::WheelUp => wheel.up,
+        Binding::WheelDown => wheel.down,
+        _ if just_pressed(binding) => 1.0,
+        _ => 0.0,
+    };
+
+    *state = ActionState::default();
+    for ActionBindings { action, inputs } in bindings.bindings.iter() {
+        if inputs.iter().any(pressed) {
+            state.pressed.insert(*action);
+        }
+        if inputs.iter().any(just_pressed) {
+            state.just_pressed.insert(*action);
+        }
+        if inputs.iter().any(just_released) && !inputs.iter().any(pressed) {
+            state.just_released.insert(*action);
+        }
+        let value: f32 = inputs.iter().map(value).sum();
+        if value != 0.0 {
+            state.values.insert(*action, value);
+        }
+    }
+}
+
+/// Below this the sticks count as centered, worn out sticks never quite go back to zero
+const STICK_DEAD_ZONE: f32 = 0.2;
+/// How fast the left stick moves the virtual cursor, in pixels per second
+const CURSOR_SPEED: f32 = 700.0;
+
+/// The analog part of the gamepads, which doesn't fit in on/off actions
+#[derive(Resource, Debug, Default, Clone, Copy)]
+pub struct GamepadSticks {
+    pub left: Vec2,
+    pub right: Vec2,
+    /// Right trigger minus left trigger
+    pub zoom: f32,
+}
+
+fn update_gamepad_sticks(
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    button_axes: Res<Axis<GamepadButton>>,
+    mut sticks: ResMut<GamepadSticks>,
+) {
+    let stick = |gamepad, x, y| {
+        let value = Vec2::new(
+            axes.get(GamepadAxis::new(gamepad, x)).unwrap_or_default(),
+            axes.get(GamepadAxis::new(gamepad, y)).unwrap_or_default(),
+        );
+        if value.length() < STICK_DEAD_ZONE {
+            Vec2::ZERO
+        } else {
+            value
+        }
+    };
+    let trigger = |gamepad, button_type| {
+        button_axes
+            .get(GamepadButton::new(gamepad, button_type))
+            .unwrap_or_default()
+    };
+
+    *sticks = GamepadSticks::default();
+    for gamepad in gamepads.iter() {
+        sticks.left += stick(
+            gamepad,
+            GamepadAxisType::LeftStickX,
+            GamepadAxisType::LeftStickY,
+        );
+        sticks.right += stick(
+            gamepad,
+            GamepadAxisType::RightStickX,
+            GamepadAxisType::RightStickY,
+        );
+        sticks.zoom += trigger(gamepad, GamepadButtonType::RightTrigger2)
+            - trigger(gamepad, GamepadButtonType::LeftTrigger2);
+    }
+}
+
+/// Where the player is pointing in the window, moved by the mouse or by the left stick.
+/// Use this instead of `Window::cursor_position` so both work
+#[derive(Resource, Debug, Default, Clone, Copy)]
+pub struct GameCursor {
+    pub position: Option<Vec2>,
+    /// The stick moved last, so the mouse is probably not where the player is looking
+    pub from_gamepad: bool,
+}
+
+fn update_game_cursor(
+    mut window: Query<&mut Window, With<PrimaryWindow>>,
+    mut cursor_moved: EventReader<CursorMoved>,
+    sticks: Res<GamepadSticks>,
+    time: Res<Time<Real>>,
+    mut cursor: ResMut<GameCursor>,
+) {
+    let Ok(mut window) = window.get_single_mut() else {
+        return;
+    };
+    let window_size = Vec2::new(window.width(), window.height());
+    if cursor_moved.read().count() > 0 {
+        cursor.from_gamepad = false;
+    } else if sticks.left != Vec2::ZERO {
+        let position = cursor.position.unwrap_or(window_size / 2.0);
+        let delta = sticks.left * Vec2::new(1.0, -1.0) * CURSOR_SPEED * time.delta_seconds();
+        cursor.position = Some((position + delta).clamp(Vec2::ZERO, window_size));
+        cursor.from_gamepad = true;
+    }
+    if !cursor.from_gamepad {
+        cursor.position = window.cursor_position();
+    }
+    if window.cursor.visible == cursor.from_gamepad {
+        window.cursor.visible = !cursor.from_gamepad;
+    }
+}
+
+#[derive(Component)]
+struct VirtualCursor;
+
+const VIRTUAL_CURSOR_SIZE: f32 = 16.0;
+
+fn spawn_virtual_cursor(mut commands: Commands) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Px(VIRTUAL_CURSOR_SIZE),
+                height: Val::Px(VIRTUAL_CURSOR_SIZE),
+                ..default()
+            },
+            background_color: Color::WHITE.into(),
+            focus_policy: FocusPolicy::Pass,
+            z_index: ZIndex::Global(100),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        Outline::new(Val::Px(2.0), Val::ZERO, Color::BLACK),
+        VirtualCursor,
+    ));
+}
+
+fn draw_virtual_cursor(
+    cursor: Res<GameCursor>,
+    ui_scale: Res<UiScale>,
+    mut query: Query<(&mut Style, &mut Visibility), With<VirtualCursor>>,
+) {
+    for (mut style, mut visibility) in query.iter_mut() {
+        match (cursor.position, cursor.from_gamepad) {
+            (Some(position), true) => {
+                // ui positions get scaled, the cursor position doesn't
+                let position = position / ui_scale.0;
+                style.left = Val::Px(position.x - VIRTUAL_CURSOR_SIZE / 2.0);
+                style.top = Val::Px(position.y - VIRTUAL_CURSOR_SIZE / 2.0);
+                *visibility = Visibility::Visible;
+            }
+            _ => *visibility = Visibility::Hidden,
+        }
+    }
+}
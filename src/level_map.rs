@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::tile::{grid_to_world, TileRegistry};
+use crate::{LevelScene, PointOfInterest};
+
+/// Characters for the tiles every level has, `map_legend` can add more or change these
+pub const DEFAULT_LEGEND: [(char, &str); 4] =
+    [('#', "Pole"), ('^', "Spike"), ('O', "Hole"), ('C', "Chest")];
+/// Where a hero starts, the first one on the map is the first hero in `heros`
+pub const HERO: char = 'H';
+/// The last target of a hero, the first one on the map is for the first hero in `heros`
+pub const TARGET: char = 'T';
+/// Characters that leave the cell empty
+pub const EMPTY: [char; 2] = ['.', ' '];
+/// Picked in this order for tiles that don't have a character yet when saving as a map
+const SPARE_CHARACTERS: &str = "abcdefgijklmnopqrsuvwxyzABDEFGIJKLMNPQRSUVWXYZ0123456789";
+
+/// The legend of the level, with the defaults it doesn't change
+fn legend(scene: &LevelScene) -> HashMap<char, String> {
+    DEFAULT_LEGEND
+        .iter()
+        .map(|(character, name)| (*character, name.to_string()))
+        .chain(scene.map_legend.clone())
+        .collect()
+}
+
+/// Puts the tiles of the `map` in `points_of_interest_map` and the heroes on their `H` and `T`.
+/// Runs before the `points_of_interest` list is added, so the list wins where both have a tile.
+pub fn read_map(scene: &mut LevelScene, tiles: &TileRegistry) -> Result<(), String> {
+    let Some(map) = scene.map.take() else {
+        return Ok(());
+    };
+    let legend = legend(scene);
+    let mut heros = Vec::new();
+    let mut targets = Vec::new();
+    // rows go down the map, but y points up in the world
+    for (row, line) in map.lines().enumerate() {
+        for (column, character) in line.chars().enumerate() {
+            let position = scene.map_origin + IVec2::new(column as i32, -(row as i32));
+            if let Some(name) = legend.get(&character) {
+                let tile = tiles
+                    .get(name)
+                    .ok_or_else(|| format!("map legend {character:?}: no tile called {name:?}"))?;
+                scene.points_of_interest_map.insert(position, tile);
+            } else if character == HERO {
+                heros.push(position);
+            } else if character == TARGET {
+                targets.push(position);
+            } else if !EMPTY.contains(&character) {
+                return Err(format!(
+                    "map has {character:?} at line {}, which is not in the legend",
+                    row + 1
+                ));
+            }
+        }
+    }
+    if heros.len() > scene.heros.len() || targets.len() > scene.heros.len() {
+        return Err(format!(
+            "map has more {HERO} or {TARGET} than the {} heroes of the level",
+            scene.heros.len()
+        ));
+    }
+    for (hero, position) in scene.heros.iter_mut().zip(heros) {
+        hero.position = grid_to_world(position);
+    }
+    for (hero, position) in scene.heros.iter_mut().zip(targets) {
+        hero.targets.push(grid_to_world(position));
+    }
+    scene.map = Some(map);
+    Ok(())
+}
+
+/// Moves every tile into the `map`, for writing the level back in that form.
+/// The heroes stay in `heros`, they don't have to stand on the grid.
+pub fn to_map_form(scene: &mut LevelScene, tiles: &TileRegistry) -> Result<(), String> {
+    let mut characters: HashMap<String, char> = legend(scene)
+        .into_iter()
+        .map(|(character, name)| (name, character))
+        .collect();
+    let spare: Vec<_> = SPARE_CHARACTERS
+        .chars()
+        .filter(|character| !scene.map_legend.contains_key(character))
+        .collect();
+    let mut spare = spare.into_iter();
+    let mut cells = HashMap::new();
+    for (position, tile) in scene.points_of_interest_map.iter() {
+        let name = &tiles[*tile].name;
+        let character = match characters.get(name) {
+            Some(character) => *character,
+            None => {
+                let character = spare.next().ok_or_else(|| {
+                    format!(
+                        "it has more kinds of tiles than the {} characters a map can give them",
+                        SPARE_CHARACTERS.len()
+                    )
+                })?;
+                scene.map_legend.insert(character, name.clone());
+                characters.insert(name.clone(), character);
+                character
+            }
+        };
+        cells.insert(*position, character);
+    }
+    let (Some(min), Some(max)) = (
+        scene
+            .points_of_interest_map
+            .keys()
+            .copied()
+            .reduce(IVec2::min),
+        scene
+            .points_of_interest_map
+            .keys()
+            .copied()
+            .reduce(IVec2::max),
+    ) else {
+        scene.map = None;
+        return Ok(());
+    };
+    let mut map = String::new();
+    for y in (min.y..=max.y).rev() {
+        let line: String = (min.x..=max.x)
+            .map(|x| cells.get(&IVec2::new(x, y)).copied().unwrap_or(EMPTY[0]))
+            .collect();
+        map.push_str(&line);
+        map.push('\n');
+    }
+    scene.map = Some(map);
+    scene.map_origin = IVec2::new(min.x, max.y);
+    scene.points_of_interest.clear();
+    Ok(())
+}
+
+/// Moves every tile into the `points_of_interest` list, for writing the level back in that form
+pub fn to_list_form(scene: &mut LevelScene, tiles: &TileRegistry) {
+    let mut list: Vec<_> = scene
+        .points_of_interest_map
+        .iter()
+        .map(|(position, tile)| (*position, tiles[*tile].name.clone()))
+        .collect();
+    list.sort_by_key(|(position, name)| (name.clone(), -position.y, position.x));
+    scene.points_of_interest = list
+        .into_iter()
+        .map(|(position, name)| PointOfInterest::Tile(position, name))
+        .collect();
+    scene.map = None;
+    scene.map_origin = IVec2::ZERO;
+    scene.map_legend.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::campaign::{parse_level, BUILT_IN_LEVELS};
+
+    /// A level with `heros` heroes and the rest of it in `extra`
+    fn level(heros: usize, extra: &str) -> String {
+        let mut data = format!(
+            "level_name = \"Test\"\nbackground_texture = \"EvilGrass.png\"\nmusic = \"Battle_1.ogg\"\n{extra}\n"
+        );
+        if heros == 0 {
+            data.push_str("heros = []\n");
+        }
+        for _ in 0..heros {
+            data.push_str(
+                "\n[[heros]]\nhero_type = \"JohnHeron\"\ntargets = [[0, 640]]\nspeed = 100\nmax_health = 100\n",
+            );
+        }
+        data
+    }
+
+    fn reparse(scene: &LevelScene, tiles: &TileRegistry) -> LevelScene {
+        parse_level(&toml::to_string(scene).unwrap(), tiles).unwrap()
+    }
+
+    #[test]
+    fn built_in_levels_go_from_map_to_list_and_back() {
+        let tiles = TileRegistry::built_in();
+        for data in BUILT_IN_LEVELS {
+            let original = parse_level(data, &tiles).unwrap();
+
+            let mut as_map = original.clone();
+            to_map_form(&mut as_map, &tiles).unwrap();
+            assert!(as_map.points_of_interest.is_empty());
+            let from_map = reparse(&as_map, &tiles);
+            assert_eq!(
+                from_map.points_of_interest_map,
+                original.points_of_interest_map
+            );
+
+            let mut as_list = from_map.clone();
+            to_list_form(&mut as_list, &tiles);
+            assert_eq!(as_list.map, None);
+            let from_list = reparse(&as_list, &tiles);
+            assert_eq!(
+                from_list.points_of_interest_map,
+                original.points_of_interest_map
+            );
+
+            let mut as_map_again = from_list;
+            to_map_form(&mut as_map_again, &tiles).unwrap();
+            assert_eq!(as_map_again.map, as_map.map);
+            assert_eq!(as_map_again.map_origin, as_map.map_origin);
+        }
+    }
+
+    #[test]
+    fn map_characters_are_placed_from_the_origin() {
+        let tiles = TileRegistry::built_in();
+        let data = level(0, "map_origin = [2, 5]\nmap = \"#.^\\n.O\\n\"");
+        let scene = parse_level(&data, &tiles).unwrap();
+        let expected: HashMap<_, _> = [
+            (IVec2::new(2, 5), tiles.get("Pole").unwrap()),
+            (IVec2::new(4, 5), tiles.get("Spike").unwrap()),
+            (IVec2::new(3, 4), tiles.get("Hole").unwrap()),
+        ]
+        .into();
+        assert_eq!(scene.points_of_interest_map, expected);
+    }
+
+    #[test]
+    fn unknown_characters_are_refused() {
+        let data = level(0, "map = \"#.\\n.?\\n\"");
+        let err = parse_level(&data, &TileRegistry::built_in()).unwrap_err();
+        assert!(err.contains("'?'") && err.contains("line 2"), "{err}");
+    }
+
+    #[test]
+    fn legend_needs_known_tiles() {
+        let data = level(0, "map = \"x\\n\"\nmap_legend = { x = \"Lava\" }");
+        let err = parse_level(&data, &TileRegistry::built_in()).unwrap_err();
+        assert!(err.contains("Lava"), "{err}");
+    }
+
+    #[test]
+    fn heroes_start_on_h_and_walk_to_t() {
+        let tiles = TileRegistry::built_in();
+        let data = level(2, "map = \"H.T\\n.H.\\nT..\\n\"");
+        let scene = parse_level(&data, &tiles).unwrap();
+        assert_eq!(scene.heros[0].position, grid_to_world(IVec2::new(0, 0)));
+        assert_eq!(scene.heros[1].position, grid_to_world(IVec2::new(1, -1)));
+        // after the targets from the list
+        assert_eq!(
+            scene.heros[0].targets,
+            vec![Vec2::new(0.0, 640.0), grid_to_world(IVec2::new(2, 0))]
+        );
+        assert_eq!(
+            scene.heros[1].targets,
+            vec![Vec2::new(0.0, 640.0), grid_to_world(IVec2::new(0, -2))]
+        );
+        assert!(scene.points_of_interest_map.is_empty());
+    }
+
+    #[test]
+    fn more_markers_than_heroes_are_refused() {
+        let data = level(1, "map = \"HH\\n\"");
+        assert!(parse_level(&data, &TileRegistry::built_in()).is_err());
+    }
+
+    #[test]
+    fn too_many_kinds_of_tiles_for_a_map_are_refused() {
+        let mut tiles = TileRegistry::built_in();
+        let kinds = SPARE_CHARACTERS.len() + DEFAULT_LEGEND.len() + 1;
+        let extra: String = (0..kinds).map(|i| format!("[Extra{i}]\n")).collect();
+        tiles.extend(&extra).unwrap();
+        let mut scene = LevelScene::default();
+        for i in 0..kinds {
+            let tile = tiles.get(&format!("Extra{i}")).unwrap();
+            scene
+                .points_of_interest_map
+                .insert(IVec2::new(i as i32, 0), tile);
+        }
+        let err = to_map_form(&mut scene, &tiles).unwrap_err();
+        assert!(err.contains("more kinds of tiles"), "{err}");
+    }
+}
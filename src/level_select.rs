@@ -1,21 +1,47 @@
-use std::sync::OnceLock;
+use std::collections::BTreeMap;
+use std::time::Duration;
 
+use bevy::math::I64Vec2;
 use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::render::texture::ImageSampler;
+use serde::{Deserialize, Serialize};
 
-use crate::{despawn_screen, GameState, LevelScene, BGM};
+use crate::campaign::{Campaign, Campaigns};
+use crate::config;
+use crate::locale::LocalizedText;
+use crate::music::PlayMusic;
+use crate::palette::PaletteColors;
+use crate::settings::GameSettings;
+use crate::tile::{world_to_grid, TileRegistry};
+use crate::widget::{button_bundle, ButtonColors, SpawnButton};
+use crate::{despawn_screen, GameState, LevelScene};
 
 pub struct LevelSelectPlugin;
 
 impl Plugin for LevelSelectPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<LevelsWon>()
-            .add_systems(OnEnter(GameState::LevelSelect), (setup, reenter_level))
+        app.insert_resource(config::load::<LevelProgress>(LevelProgress::FILE_NAME))
+            .init_resource::<LevelSelectPage>()
+            .init_resource::<SelectedCampaign>()
+            .add_systems(
+                OnEnter(GameState::LevelSelect),
+                (setup, update_level_cards).chain(),
+            )
             .add_systems(
                 Update,
                 (
-                    button_pressed.run_if(in_state(GameState::LevelSelect)),
-                    back_button_pressed.run_if(in_state(GameState::LevelSelect)),
-                ),
+                    button_pressed,
+                    back_button_pressed,
+                    page_button_pressed,
+                    campaign_button_pressed,
+                    update_level_cards.run_if(
+                        resource_changed::<LevelSelectPage>
+                            .or_else(resource_changed::<SelectedCampaign>),
+                    ),
+                )
+                    .run_if(in_state(GameState::LevelSelect)),
             )
             .add_systems(
                 OnExit(GameState::LevelSelect),
@@ -30,20 +56,112 @@ pub struct LevelSelectWindow;
 #[derive(Component)]
 pub struct Level(pub usize);
 
-#[derive(Resource, Debug, Default, DerefMut, Deref)]
-pub struct LevelsWon(pub [bool; NUMBER_OF_LEVELS]);
+/// Which levels have been won and how fast, saved to `config/progress.toml`
+#[derive(Resource, Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LevelProgress {
+    /// By campaign id, the record of level `n` is at index `n - 1`
+    campaigns: BTreeMap<String, Vec<LevelRecord>>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LevelRecord {
+    pub won: bool,
+    /// Fastest win in seconds of game time
+    pub best_time: Option<f32>,
+}
+
+impl LevelProgress {
+    pub const FILE_NAME: &'static str = "progress.toml";
+
+    pub fn record(&self, campaign: &Campaign, level: usize) -> LevelRecord {
+        self.campaigns
+            .get(&campaign.id)
+            .and_then(|levels| levels.get(level - 1))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn won(&self, campaign: &Campaign, level: usize) -> bool {
+        self.record(campaign, level).won
+    }
+
+    /// A level can be played once the one before it is won
+    pub fn unlocked(&self, campaign: &Campaign, level: usize) -> bool {
+        level == 1 || self.won(campaign, level - 1)
+    }
+
+    /// The first level that hasn't been won, `None` once all of them are
+    pub fn next_level(&self, campaign: &Campaign) -> Option<usize> {
+        (1..=campaign.levels.len()).find(|&level| !self.won(campaign, level))
+    }
+
+    pub fn record_win(&mut self, campaign: &Campaign, level: usize, time: Duration) {
+        let levels = self.campaigns.entry(campaign.id.clone()).or_default();
+        if levels.len() < level {
+            levels.resize(level, LevelRecord::default());
+        }
+        let record = &mut levels[level - 1];
+        record.won = true;
+        let time = time.as_secs_f32();
+        record.best_time = Some(record.best_time.map_or(time, |best| best.min(time)));
+    }
+}
+
+/// The page of level cards that is shown, starting at 0
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+struct LevelSelectPage(usize);
+
+/// Index in [`Campaigns`] of the campaign whose levels are shown, and continued from the menu
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub struct SelectedCampaign(pub usize);
+
+const CARDS_PER_PAGE: usize = 8;
+/// Width and height of the map previews on the level cards
+const MINIMAP_SIZE: f32 = 120.0;
+
+fn page_count(campaign: &Campaign) -> usize {
+    campaign.levels.len().div_ceil(CARDS_PER_PAGE)
+}
+
+/// The page with the level that is up next
+fn next_level_page(progress: &LevelProgress, campaign: &Campaign) -> usize {
+    let next_level = progress
+        .next_level(campaign)
+        .unwrap_or(campaign.levels.len());
+    (next_level - 1) / CARDS_PER_PAGE
+}
+
+#[derive(Component)]
+struct LevelGrid;
+
+#[derive(Component)]
+struct PageLabel;
+
+/// Hidden when the campaign fits on one page
+#[derive(Component)]
+struct Pager;
+
+/// Tab for the campaign at this index in [`Campaigns`]
+#[derive(Component)]
+struct CampaignButton(usize);
 
+/// Goes this many pages forward
 #[derive(Component)]
-pub struct ReenterLevel(pub usize);
+struct PageButton(isize);
 
 #[derive(Component)]
 struct BackToMainButton;
 
 fn setup(
     mut commands: Commands,
-    levels_won: Res<LevelsWon>,
-    mut bgm_query: Query<(&mut BGM, Entity)>,
-    asset_server: Res<AssetServer>,
+    progress: Res<LevelProgress>,
+    campaigns: Res<Campaigns>,
+    mut selected: ResMut<SelectedCampaign>,
+    mut page: ResMut<LevelSelectPage>,
+    settings: Res<GameSettings>,
+    mut music: EventWriter<PlayMusic>,
 ) {
     commands.spawn((
         Camera2dBundle {
@@ -56,27 +174,24 @@ fn setup(
         LevelSelectWindow,
     ));
 
-    if let Ok((mut bgm, entity)) = bgm_query.get_single_mut() {
-        if bgm.0 != "music/Main_menu.ogg" {
-            commands
-                .entity(entity)
-                .remove::<AudioSink>()
-                .insert(asset_server.load::<AudioSource>("music/Main_menu.ogg"));
-            bgm.0 = "music/Main_menu.ogg".to_string();
-        }
+    music.send(PlayMusic::looping("music/Main_menu.ogg"));
+
+    // a level pack may have been removed since it was picked
+    if selected.0 >= campaigns.len() {
+        selected.0 = 0;
     }
+    // open on the level that is up next
+    page.0 = next_level_page(&progress, &campaigns[selected.0]);
 
     commands
         .spawn((
             NodeBundle {
                 style: Style {
-                    top: Val::Px(50.0),
-                    display: Display::Grid,
-                    grid_template_columns: RepeatedGridTrack::flex(4, 1.0),
-                    // grid_template_rows: RepeatedGridTrack::flex(4, 1.0),
+                    width: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
                     row_gap: Val::Px(20.0),
-                    column_gap: Val::Px(20.0),
-                    justify_self: JustifySelf::Center,
+                    padding: UiRect::top(Val::Px(50.0)),
                     ..default()
                 },
                 ..default()
@@ -84,142 +199,363 @@ fn setup(
             LevelSelectWindow,
         ))
         .with_children(|parent| {
-            for i in 1..=NUMBER_OF_LEVELS {
+            if campaigns.len() > 1 {
+                spawn_campaign_tabs(parent, &campaigns);
+            }
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        display: Display::Grid,
+                        grid_template_columns: RepeatedGridTrack::auto(4),
+                        row_gap: Val::Px(20.0),
+                        column_gap: Val::Px(20.0),
+                        ..default()
+                    },
+                    ..default()
+                },
+                LevelGrid,
+            ));
+            spawn_pager(parent, settings.palette.colors());
+        });
+
+    commands
+        .spawn_button(
+            LocalizedText::key("common.back"),
+            50.0,
+            ButtonColors::MENU,
+            Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(5.0),
+                left: Val::Px(5.0),
+                ..default()
+            },
+        )
+        .insert((BackToMainButton, LevelSelectWindow));
+}
+
+fn spawn_campaign_tabs(parent: &mut ChildBuilder, campaigns: &Campaigns) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                flex_wrap: FlexWrap::Wrap,
+                justify_content: JustifyContent::Center,
+                column_gap: Val::Px(10.0),
+                row_gap: Val::Px(10.0),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            for (index, campaign) in campaigns.iter().enumerate() {
                 parent
-                    .spawn((
-                        ButtonBundle {
-                            style: Style {
-                                align_self: AlignSelf::Center,
-                                ..default()
-                            },
-                            background_color: if levels_won[i - 1] {
-                                Color::GOLD
-                            } else {
-                                Color::WHITE
-                            }
-                            .into(),
+                    .spawn_button(
+                        campaign.name.text(),
+                        30.0,
+                        ButtonColors::MENU,
+                        Style {
+                            padding: UiRect::axes(Val::Px(15.0), Val::Px(5.0)),
                             ..default()
                         },
-                        Level(i),
-                    ))
-                    .with_children(|parent| {
-                        parent.spawn(
-                            TextBundle::from_section(
-                                format!("Level {i}"),
-                                TextStyle {
-                                    font_size: 50.0,
-                                    color: Color::PURPLE,
-                                    ..default()
-                                },
-                            )
-                            .with_no_wrap(),
-                        );
-                    });
+                    )
+                    .insert(CampaignButton(index));
             }
         });
+}
 
-    commands
+fn spawn_pager(parent: &mut ChildBuilder, palette: &PaletteColors) {
+    parent
         .spawn((
-            ButtonBundle {
+            NodeBundle {
                 style: Style {
-                    position_type: PositionType::Absolute,
-                    top: Val::Px(5.0),
-                    left: Val::Px(5.0),
+                    align_items: AlignItems::Center,
+                    column_gap: Val::Px(20.0),
                     ..default()
                 },
-                background_color: Color::PINK.into(),
                 ..default()
             },
-            BackToMainButton,
-            LevelSelectWindow,
+            Pager,
         ))
         .with_children(|parent| {
-            parent.spawn(
+            let style = Style {
+                padding: UiRect::horizontal(Val::Px(15.0)),
+                ..default()
+            };
+            parent
+                .spawn_button("<", 40.0, ButtonColors::MENU, style.clone())
+                .insert(PageButton(-1));
+            parent.spawn((
                 TextBundle::from_section(
-                    "Back",
+                    String::new(),
                     TextStyle {
-                        font_size: 50.0,
-                        color: Color::WHITE,
+                        font_size: 40.0,
+                        color: palette.level_text,
                         ..default()
                     },
-                )
-                .with_no_wrap(),
-            );
+                ),
+                LocalizedText::key("level_select.page"),
+                PageLabel,
+            ));
+            parent
+                .spawn_button(">", 40.0, ButtonColors::MENU, style)
+                .insert(PageButton(1));
+        });
+}
+
+/// Fills the grid with the cards on the current page of the selected campaign
+fn update_level_cards(
+    mut commands: Commands,
+    grid: Query<Entity, With<LevelGrid>>,
+    mut label: Query<&mut LocalizedText, With<PageLabel>>,
+    mut pager: Query<&mut Style, With<Pager>>,
+    mut tabs: Query<(&CampaignButton, &mut ButtonColors)>,
+    page: Res<LevelSelectPage>,
+    selected: Res<SelectedCampaign>,
+    campaigns: Res<Campaigns>,
+    progress: Res<LevelProgress>,
+    settings: Res<GameSettings>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let Ok(grid) = grid.get_single() else {
+        return;
+    };
+    let campaign = &campaigns[selected.0];
+    if let Ok(mut label) = label.get_single_mut() {
+        *label = LocalizedText::key("level_select.page")
+            .with_arg("page", page.0 + 1)
+            .with_arg("pages", page_count(campaign));
+    }
+    if let Ok(mut pager) = pager.get_single_mut() {
+        pager.display = if page_count(campaign) > 1 {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+    for (tab, mut colors) in tabs.iter_mut() {
+        *colors = if tab.0 == selected.0 {
+            ButtonColors::GAME
+        } else {
+            ButtonColors::MENU
+        };
+    }
+    let palette = settings.palette.colors();
+    let text_style = |font_size| TextStyle {
+        font_size,
+        color: palette.level_text,
+        ..default()
+    };
+    let first = page.0 * CARDS_PER_PAGE + 1;
+    let last = (first + CARDS_PER_PAGE - 1).min(campaign.levels.len());
+
+    commands
+        .entity(grid)
+        .despawn_descendants()
+        .with_children(|parent| {
+            for (level, scene) in (first..=last).zip(&campaign.levels[first - 1..]) {
+                let record = progress.record(campaign, level);
+                let unlocked = progress.unlocked(campaign, level);
+                let style = Style {
+                    width: Val::Px(260.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(5.0),
+                    padding: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                };
+                let mut card = if unlocked {
+                    let colors = ButtonColors {
+                        background: if record.won {
+                            palette.level_won
+                        } else {
+                            palette.level_not_won
+                        },
+                        text: palette.level_text,
+                    };
+                    let mut card = parent.spawn(button_bundle(colors, style));
+                    card.insert(Level(level));
+                    card
+                } else {
+                    parent.spawn(NodeBundle {
+                        style: Style {
+                            align_items: AlignItems::Center,
+                            ..style
+                        },
+                        background_color: palette.level_not_won.with_a(0.4).into(),
+                        ..default()
+                    })
+                };
+                card.with_children(|parent| {
+                    parent.spawn((
+                        TextBundle::from_section(String::new(), text_style(40.0)),
+                        LocalizedText::key("level_select.level").with_arg("number", level),
+                    ));
+                    parent.spawn((
+                        TextBundle::from_section(String::new(), text_style(25.0)),
+                        scene.level_name.text(),
+                    ));
+                    spawn_minimap(parent, scene, &campaign.tiles, &mut images);
+                    // said in words, not only by the colour of the card
+                    let result = match (unlocked, record.best_time) {
+                        (false, _) => LocalizedText::key("level_select.locked"),
+                        (true, Some(time)) => LocalizedText::key("level_select.best")
+                            .with_arg("seconds", format!("{time:.1}")),
+                        (true, None) => LocalizedText::key("level_select.not_won"),
+                    };
+                    parent.spawn((
+                        TextBundle::from_section(String::new(), text_style(25.0)),
+                        result,
+                    ));
+                });
+            }
         });
 }
 
+/// A small map of the points of interest, with the heroes in white where they start.
+/// Drawn into a single image, so a big map doesn't cost a node for every tile.
+fn spawn_minimap(
+    parent: &mut ChildBuilder,
+    scene: &LevelScene,
+    tiles: &TileRegistry,
+    images: &mut Assets<Image>,
+) {
+    let style = Style {
+        width: Val::Px(MINIMAP_SIZE),
+        height: Val::Px(MINIMAP_SIZE),
+        align_self: AlignSelf::Center,
+        ..default()
+    };
+    // an empty level has nothing to show, it only keeps the cards the same size
+    let Some(image) = minimap_image(scene, tiles) else {
+        parent.spawn(NodeBundle { style, ..default() });
+        return;
+    };
+    parent.spawn(ImageBundle {
+        style,
+        image: UiImage::new(images.add(image)),
+        ..default()
+    });
+}
+
+fn minimap_image(scene: &LevelScene, tiles: &TileRegistry) -> Option<Image> {
+    let heros: Vec<_> = scene
+        .heros
+        .iter()
+        .map(|hero| world_to_grid(hero.position))
+        .collect();
+    let positions = scene
+        .points_of_interest_map
+        .keys()
+        .copied()
+        .chain(heros.iter().copied());
+    let (min, max) = positions.fold(None, |bounds, position| match bounds {
+        None => Some((position, position)),
+        Some((min, max)) => Some((IVec2::min(min, position), IVec2::max(max, position))),
+    })?;
+    let pixels = MINIMAP_SIZE as usize;
+    // i64, so levels that are far apart can't overflow
+    let size = (max.as_i64vec2() - min.as_i64vec2() + I64Vec2::ONE).as_vec2();
+    // whole pixels keep the cells sharp, unless the map is too big for that
+    let cell = MINIMAP_SIZE / size.max_element();
+    let cell = if cell >= 1.0 { cell.floor() } else { cell };
+    let offset = ((Vec2::splat(MINIMAP_SIZE) - size * cell) / 2.0).floor();
+
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: pixels as u32,
+            height: pixels as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    image.sampler = ImageSampler::nearest();
+    let mut fill = |from: Vec2, to: Vec2, color: Color| {
+        let from = from.as_uvec2().min(UVec2::splat(pixels as u32));
+        let to = to
+            .as_uvec2()
+            .max(from + UVec2::ONE)
+            .min(UVec2::splat(pixels as u32));
+        for y in from.y..to.y {
+            // images go down from the top, the world has y pointing up
+            let row = pixels - 1 - y as usize;
+            for x in from.x..to.x {
+                let index = (row * pixels + x as usize) * 4;
+                image.data[index..index + 4].copy_from_slice(&color.as_rgba_u8());
+            }
+        }
+    };
+    fill(
+        offset,
+        offset + size * cell,
+        Color::rgba(0.0, 0.0, 0.0, 0.3),
+    );
+    let mut cell_at = |position: IVec2, color: Color| {
+        let from = offset + (position.as_i64vec2() - min.as_i64vec2()).as_vec2() * cell;
+        fill(from, from + Vec2::splat(cell), color);
+    };
+    for (position, tile) in scene.points_of_interest_map.iter() {
+        cell_at(*position, tiles[*tile].minimap_color);
+    }
+    for position in heros.iter() {
+        cell_at(*position, Color::WHITE);
+    }
+    Some(image)
+}
+
 fn button_pressed(
     query: Query<(&Interaction, &Level)>,
+    campaigns: Res<Campaigns>,
+    selected: Res<SelectedCampaign>,
     mut scene: ResMut<LevelScene>,
     mut state: ResMut<NextState<GameState>>,
 ) {
     for (interaction, level) in query.iter() {
         if *interaction == Interaction::Pressed {
-            *scene = load_scene(level.0);
-            state.set(GameState::Gaming);
+            if let Some(level) = campaigns.scene(selected.0, level.0) {
+                *scene = level;
+                state.set(GameState::Gaming);
+            }
         }
     }
 }
 
-fn back_button_pressed(
-    query: Query<&Interaction, With<BackToMainButton>>,
-    mut state: ResMut<NextState<GameState>>,
+fn page_button_pressed(
+    query: Query<(&Interaction, &PageButton), Changed<Interaction>>,
+    campaigns: Res<Campaigns>,
+    selected: Res<SelectedCampaign>,
+    mut page: ResMut<LevelSelectPage>,
 ) {
-    for interaction in query.iter() {
+    for (interaction, button) in query.iter() {
         if *interaction == Interaction::Pressed {
-            state.set(GameState::MainMenu);
+            let pages = page_count(&campaigns[selected.0]) as isize;
+            page.0 = (page.0 as isize + button.0).rem_euclid(pages) as usize;
         }
     }
 }
 
-fn reenter_level(
-    query: Query<&ReenterLevel>,
-    mut scene: ResMut<LevelScene>,
-    mut state: ResMut<NextState<GameState>>,
+fn campaign_button_pressed(
+    query: Query<(&Interaction, &CampaignButton), Changed<Interaction>>,
+    campaigns: Res<Campaigns>,
+    progress: Res<LevelProgress>,
+    mut selected: ResMut<SelectedCampaign>,
+    mut page: ResMut<LevelSelectPage>,
 ) {
-    for q in query.iter() {
-        *scene = load_scene(q.0);
-        state.set(GameState::Gaming);
-    }
-}
-
-fn load_scene(id: usize) -> LevelScene {
-    if let Some(data) = LEVEL_DATA.get() {
-        return data[id - 1].clone();
+    for (interaction, button) in query.iter() {
+        if *interaction == Interaction::Pressed && selected.0 != button.0 {
+            selected.0 = button.0;
+            page.0 = next_level_page(&progress, &campaigns[button.0]);
+        }
     }
-
-    let res: Vec<LevelScene> = (1..=NUMBER_OF_LEVELS)
-        .map(|id| {
-            let s = level(id);
-
-            let mut scene = toml::from_str::<LevelScene>(s).unwrap();
-            for (position, tile) in scene.points_of_interest.iter() {
-                scene.points_of_interest_map.insert(*position, *tile);
-            }
-
-            scene.level = id;
-            scene
-        })
-        .collect();
-    let out = res[id - 1].clone();
-    LEVEL_DATA.set(res).unwrap();
-    out
 }
 
-const fn level(id: usize) -> &'static str {
-    match id {
-        1 => LEVEL1,
-        2 => LEVEL2,
-        3 => LEVEL3,
-        4 => LEVEL4,
-        _ => unimplemented!(),
+fn back_button_pressed(
+    query: Query<&Interaction, With<BackToMainButton>>,
+    mut state: ResMut<NextState<GameState>>,
+) {
+    for interaction in query.iter() {
+        if *interaction == Interaction::Pressed {
+            state.set(GameState::MainMenu);
+        }
     }
 }
-
-static LEVEL_DATA: OnceLock<Vec<LevelScene>> = OnceLock::new();
-
-const NUMBER_OF_LEVELS: usize = 4;
-const LEVEL1: &str = include_str!("../levels/level1.toml");
-const LEVEL2: &str = include_str!("../levels/level2.toml");
-const LEVEL3: &str = include_str!("../levels/level3.toml");
-const LEVEL4: &str = include_str!("../levels/level4.toml");
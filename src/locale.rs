@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy::ui::widget::measure_text_system;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::GameSettings;
+
+pub struct LocalePlugin;
+
+impl Plugin for LocalePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Locale>()
+            .add_systems(
+                PreUpdate,
+                follow_language.run_if(resource_changed::<GameSettings>),
+            )
+            .add_systems(
+                PostUpdate,
+                // before the text gets measured, so new text never shows up empty for a frame
+                localize_text.before(measure_text_system),
+            );
+    }
+}
+
+/// Languages that have a string file in `locales/`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Language {
+    #[default]
+    English,
+    Dutch,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::Dutch];
+
+    /// Name of the language in that language, so it can be found without being able to read
+    /// the current one
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::Dutch => "Nederlands",
+        }
+    }
+
+    fn strings(self) -> &'static str {
+        match self {
+            Language::English => include_str!("../locales/en.toml"),
+            Language::Dutch => include_str!("../locales/nl.toml"),
+        }
+    }
+}
+
+/// The strings of the chosen language, anything missing there is taken from English
+#[derive(Resource, Debug)]
+pub struct Locale {
+    language: Language,
+    strings: HashMap<String, String>,
+    fallback: HashMap<String, String>,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self::new(Language::English)
+    }
+}
+
+impl Locale {
+    pub fn new(language: Language) -> Self {
+        Self {
+            language,
+            strings: load_strings(language),
+            fallback: load_strings(Language::English),
+        }
+    }
+
+    pub fn language(&self) -> Language {
+        self.language
+    }
+
+    /// The string for `key`, or the key itself when not even English has it
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.strings
+            .get(key)
+            .or_else(|| self.fallback.get(key))
+            .map_or(key, String::as_str)
+    }
+
+    /// Fills in the `{name}` placeholders of the string for `key`
+    pub fn format(&self, key: &str, args: &[(&str, String)]) -> String {
+        args.iter()
+            .fold(self.get(key).to_string(), |text, (name, value)| {
+                text.replace(&format!("{{{name}}}"), value)
+            })
+    }
+
+    pub fn text(&self, text: &LocalizedText) -> String {
+        match text {
+            LocalizedText::Key(key, args) => self.format(key, args),
+            LocalizedText::Plain(text) => text.clone(),
+        }
+    }
+}
+
+/// Nested tables become dotted keys, so `[menu] start = ".."` is found as `menu.start`
+fn load_strings(language: Language) -> HashMap<String, String> {
+    fn flatten(prefix: &str, table: toml::Table, strings: &mut HashMap<String, String>) {
+        for (key, value) in table {
+            let key = if prefix.is_empty() {
+                key
+            } else {
+                format!("{prefix}.{key}")
+            };
+            match value {
+                toml::Value::String(text) => {
+                    strings.insert(key, text);
+                }
+                toml::Value::Table(table) => flatten(&key, table, strings),
+                value => warn!("string {key} is not text but {value}"),
+            }
+        }
+    }
+    let mut strings = HashMap::new();
+    flatten(
+        "",
+        toml::from_str(language.strings()).unwrap(),
+        &mut strings,
+    );
+    strings
+}
+
+/// Text shown to the player, the first section of the `Text` on the same entity is kept
+/// up to date with the language
+#[derive(Component, Debug, Clone, PartialEq)]
+pub enum LocalizedText {
+    /// A key in the string files, with the values for its `{placeholders}`
+    Key(String, Vec<(&'static str, String)>),
+    /// Shown as is, for names and numbers
+    Plain(String),
+}
+
+impl LocalizedText {
+    pub fn key(key: impl Into<String>) -> Self {
+        Self::Key(key.into(), Vec::new())
+    }
+
+    /// Adds a value for a placeholder, does nothing for plain text
+    pub fn with_arg(mut self, name: &'static str, value: impl ToString) -> Self {
+        if let Self::Key(_, args) = &mut self {
+            args.push((name, value.to_string()));
+        }
+        self
+    }
+}
+
+impl From<&str> for LocalizedText {
+    fn from(text: &str) -> Self {
+        Self::Plain(text.to_string())
+    }
+}
+
+impl From<String> for LocalizedText {
+    fn from(text: String) -> Self {
+        Self::Plain(text)
+    }
+}
+
+fn follow_language(settings: Res<GameSettings>, mut locale: ResMut<Locale>) {
+    if locale.language != settings.language {
+        *locale = Locale::new(settings.language);
+    }
+}
+
+fn localize_text(locale: Res<Locale>, mut query: Query<(Ref<LocalizedText>, &mut Text)>) {
+    for (localized, mut text) in query.iter_mut() {
+        if !locale.is_changed() && !localized.is_changed() {
+            continue;
+        }
+        if let Some(section) = text.sections.first_mut() {
+            section.value = locale.text(&localized);
+        }
+    }
+}
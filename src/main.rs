@@ -1,43 +1,70 @@
 #![allow(clippy::too_many_arguments)]
 #![allow(clippy::type_complexity)]
 
+pub mod campaign;
+pub mod cli;
+pub mod config;
+pub mod focus;
 pub mod game;
 pub mod info_screen;
+pub mod input;
+pub mod level_map;
 pub mod level_select;
+pub mod locale;
 pub mod main_menu;
+pub mod migration;
+pub mod music;
+pub mod palette;
+pub mod settings;
+pub mod sound;
 pub mod tile;
+pub mod tiled;
+pub mod widget;
 
+use crate::locale::LocalizedText;
+use crate::music::MusicLayer;
+use crate::settings::GameSettings;
 use crate::tile::Tile;
 use bevy::asset::AssetMetaCheck;
-use bevy::audio::PlaybackMode;
-use bevy::window::PresentMode;
-use bevy::window::WindowMode;
 use bevy::{diagnostic::FrameTimeDiagnosticsPlugin, prelude::*};
 use game::hero::Hero;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 fn main() {
+    if let Some(code) = cli::run() {
+        std::process::exit(code);
+    }
+    let settings = config::load::<GameSettings>(GameSettings::FILE_NAME);
     App::new()
         .insert_resource(AssetMetaCheck::Never)
+        .register_asset_source(campaign::MODS_SOURCE, campaign::mods_source())
         .add_plugins(
             DefaultPlugins
                 .set(ImagePlugin::default_nearest())
                 .set(WindowPlugin {
                     primary_window: Some(Window {
-                        present_mode: PresentMode::AutoVsync,
-                        mode: WindowMode::Windowed,
+                        present_mode: settings.present_mode(),
+                        mode: settings.window_mode(),
                         title: "Epic Game".to_string(),
                         ..default()
                     }),
                     ..default()
                 }),
         )
+        .insert_resource(settings)
         .init_state::<GameState>()
         .init_resource::<LevelScene>()
         .add_event::<EndGameEvent>()
-        .add_systems(Startup, start_bgm)
         .add_plugins((
+            locale::LocalePlugin,
+            campaign::CampaignPlugin,
+            input::InputPlugin,
+            focus::FocusPlugin,
+            widget::WidgetPlugin,
+            music::MusicPlugin,
+            sound::SoundPlugin,
+            settings::SettingsPlugin,
             main_menu::MenuPlugin,
             info_screen::InfoPlugin,
             level_select::LevelSelectPlugin,
@@ -56,35 +83,176 @@ pub enum GameState {
     Gaming,
 }
 
-#[derive(Resource, Component)]
-pub struct BGM(String);
-
-fn start_bgm(mut commands: Commands, asset_server: Res<AssetServer>) {
-    commands.spawn((
-        AudioBundle {
-            source: asset_server.load("music/Main_menu.ogg"),
-            settings: PlaybackSettings {
-                mode: PlaybackMode::Loop,
-                ..default()
-            },
-        },
-        BGM("music/Main_menu.ogg".to_string()),
-    ));
-}
-
 #[derive(Resource, Debug, Default, Serialize, Deserialize, Clone)]
 pub struct LevelScene {
-    pub level_name: String,
+    /// Always `migration::CURRENT_VERSION` once loaded, older files are upgraded first
+    #[serde(default)]
+    pub format_version: i64,
+    pub level_name: LevelName,
     pub background_texture: String,
     pub music: String,
+    /// Versions of the music that take over depending on how the level is going
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub music_layers: Vec<MusicLayer>,
     pub heros: Vec<Hero>,
-    pub points_of_interest: Vec<(IVec2, Tile)>,
+    /// Tiles by their name in the tile registry, one at a time or a shape of them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub points_of_interest: Vec<PointOfInterest>,
+    /// The tiles drawn with a character for each, see `level_map`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub map: Option<String>,
+    /// Grid position of the first character of the map
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub map_origin: IVec2,
+    /// Tile names for characters on the map, on top of `level_map::DEFAULT_LEGEND`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub map_legend: BTreeMap<char, String>,
+    /// Filled in from `map` and `points_of_interest` when the level is loaded
     #[serde(skip)]
     pub points_of_interest_map: HashMap<IVec2, Tile>,
+    /// Index in `Campaigns`
+    #[serde(skip)]
+    pub campaign: usize,
+    /// Counting from 1 within the campaign
     #[serde(skip)]
     pub level: usize,
 }
 
+fn is_zero(position: &IVec2) -> bool {
+    *position == IVec2::ZERO
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// An entry of `points_of_interest`, each is expanded into single tiles when the level is loaded
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PointOfInterest {
+    /// `[[x, y], "Pole"]`
+    Tile(IVec2, String),
+    /// `{ line = [[0, 2], [0, 7]], tile = "Pole" }`, both ends included
+    Line { line: [IVec2; 2], tile: String },
+    /// `{ rect = [[-16, -15], [15, 16]], tile = "Pole" }` between two opposite corners,
+    /// only the border with `outline = true`
+    Rect {
+        rect: [IVec2; 2],
+        tile: String,
+        #[serde(default, skip_serializing_if = "is_false")]
+        outline: bool,
+    },
+}
+
+impl PointOfInterest {
+    pub fn tile(&self) -> &str {
+        match self {
+            PointOfInterest::Tile(_, tile)
+            | PointOfInterest::Line { tile, .. }
+            | PointOfInterest::Rect { tile, .. } => tile,
+        }
+    }
+
+    /// How many grid positions the entry covers, without listing them
+    pub fn size(&self) -> u64 {
+        match *self {
+            PointOfInterest::Tile(..) => 1,
+            PointOfInterest::Line {
+                line: [start, end], ..
+            } => {
+                let delta = (end.as_i64vec2() - start.as_i64vec2()).abs();
+                delta.max_element() as u64 + 1
+            }
+            PointOfInterest::Rect {
+                rect: [a, b],
+                outline,
+                ..
+            } => {
+                let delta = (b.as_i64vec2() - a.as_i64vec2()).abs();
+                let (width, height) = (delta.x as u64 + 1, delta.y as u64 + 1);
+                if outline && width > 2 && height > 2 {
+                    2 * (width + height) - 4
+                } else {
+                    width.saturating_mul(height)
+                }
+            }
+        }
+    }
+
+    /// Every grid position the entry covers
+    pub fn positions(&self) -> Vec<IVec2> {
+        match *self {
+            PointOfInterest::Tile(position, _) => vec![position],
+            PointOfInterest::Line {
+                line: [start, end], ..
+            } => line_positions(start, end),
+            PointOfInterest::Rect {
+                rect: [a, b],
+                outline,
+                ..
+            } => {
+                let (min, max) = (a.min(b), a.max(b));
+                (min.y..=max.y)
+                    .flat_map(|y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+                    .filter(|position| {
+                        !outline
+                            || position.x == min.x
+                            || position.x == max.x
+                            || position.y == min.y
+                            || position.y == max.y
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Bresenham's line, so lines that are not straight or diagonal have no gaps either
+fn line_positions(start: IVec2, end: IVec2) -> Vec<IVec2> {
+    let delta = (end.as_i64vec2() - start.as_i64vec2()).abs();
+    let step = (end.as_i64vec2() - start.as_i64vec2()).signum().as_ivec2();
+    let mut error = delta.x - delta.y;
+    let mut position = start;
+    let mut positions = vec![position];
+    while position != end {
+        let double = error * 2;
+        if double > -delta.y {
+            error -= delta.y;
+            position.x += step.x;
+        }
+        if double < delta.x {
+            error += delta.x;
+            position.y += step.y;
+        }
+        positions.push(position);
+    }
+    positions
+}
+
+/// Either a key in the string files, written as `level_name = { key = ".." }`,
+/// or the name itself for levels and level packs that don't get translated
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum LevelName {
+    Key { key: String },
+    Literal(String),
+}
+
+impl Default for LevelName {
+    fn default() -> Self {
+        Self::Literal(String::new())
+    }
+}
+
+impl LevelName {
+    pub fn text(&self) -> LocalizedText {
+        match self {
+            LevelName::Key { key } => LocalizedText::key(key.as_str()),
+            LevelName::Literal(name) => name.as_str().into(),
+        }
+    }
+}
+
 #[derive(Event, Copy, Clone, Debug)]
 pub enum EndGameEvent {
     Win,
@@ -96,3 +264,91 @@ pub fn despawn_screen<T: Component>(to_despawn: Query<Entity, With<T>>, mut comm
         commands.entity(entity).despawn_recursive();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(start: [i32; 2], end: [i32; 2]) -> PointOfInterest {
+        PointOfInterest::Line {
+            line: [start.into(), end.into()],
+            tile: "Pole".to_string(),
+        }
+    }
+
+    fn rect(a: [i32; 2], b: [i32; 2], outline: bool) -> PointOfInterest {
+        PointOfInterest::Rect {
+            rect: [a.into(), b.into()],
+            tile: "Pole".to_string(),
+            outline,
+        }
+    }
+
+    fn points(positions: &[[i32; 2]]) -> Vec<IVec2> {
+        positions.iter().map(|&position| position.into()).collect()
+    }
+
+    #[test]
+    fn lines_cover_both_ends_without_gaps() {
+        let straight = line([0, 2], [0, -1]);
+        assert_eq!(
+            straight.positions(),
+            points(&[[0, 2], [0, 1], [0, 0], [0, -1]])
+        );
+        let slanted = line([0, 0], [4, 2]);
+        assert_eq!(
+            slanted.positions(),
+            points(&[[0, 0], [1, 0], [2, 1], [3, 1], [4, 2]])
+        );
+        for shape in [straight, slanted, line([3, 3], [3, 3])] {
+            assert_eq!(shape.size(), shape.positions().len() as u64);
+        }
+    }
+
+    #[test]
+    fn rects_are_filled_between_either_corners() {
+        let filled = rect([1, 1], [-1, 0], false);
+        assert_eq!(
+            filled.positions(),
+            points(&[[-1, 0], [0, 0], [1, 0], [-1, 1], [0, 1], [1, 1]])
+        );
+        assert_eq!(filled.size(), 6);
+    }
+
+    #[test]
+    fn outlines_leave_the_middle_empty() {
+        let outline = rect([0, 0], [3, 2], true);
+        let positions = outline.positions();
+        assert_eq!(positions.len(), 10);
+        assert!(!positions.contains(&IVec2::new(1, 1)));
+        assert!(!positions.contains(&IVec2::new(2, 1)));
+        for shape in [
+            outline,
+            rect([0, 0], [4, 1], true),
+            rect([2, 2], [2, 2], true),
+        ] {
+            assert_eq!(shape.size(), shape.positions().len() as u64);
+        }
+    }
+
+    #[test]
+    fn shapes_across_the_whole_grid_are_measured_without_overflow() {
+        let (min, max) = ([i32::MIN, i32::MIN], [i32::MAX, i32::MAX]);
+        assert_eq!(line(min, max).size(), 1 << 32);
+        assert_eq!(rect(min, max, false).size(), u64::MAX);
+        assert_eq!(rect(min, max, true).size(), 4 * (1 << 32) - 4);
+    }
+
+    #[test]
+    fn levels_with_huge_shapes_are_refused() {
+        let data = "\
+level_name = \"Huge\"
+background_texture = \"EvilGrass.png\"
+music = \"Battle_1.ogg\"
+heros = []
+points_of_interest = [{ rect = [[-2147483648, 0], [2147483647, 5]], tile = \"Pole\" }]
+";
+        let err = campaign::parse_level(data, &tile::TileRegistry::built_in()).unwrap_err();
+        assert!(err.contains("more than"), "{err}");
+    }
+}
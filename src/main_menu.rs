@@ -1,6 +1,12 @@
 use bevy::prelude::*;
 
-use crate::{despawn_screen, GameState, BGM};
+use crate::campaign::Campaigns;
+use crate::level_select::{LevelProgress, SelectedCampaign};
+use crate::locale::LocalizedText;
+use crate::music::PlayMusic;
+use crate::settings::{GameSettings, SettingsState};
+use crate::widget::{ButtonColors, SpawnButton};
+use crate::{despawn_screen, GameState, LevelScene};
 
 pub struct MenuPlugin;
 
@@ -10,9 +16,10 @@ impl Plugin for MenuPlugin {
             .add_systems(
                 Update,
                 (
-                    start_game.run_if(in_state(GameState::MainMenu)),
-                    open_info.run_if(in_state(GameState::MainMenu)),
-                ),
+                    menu_button_pressed,
+                    recolor_title.run_if(resource_changed::<GameSettings>),
+                )
+                    .run_if(in_state(GameState::MainMenu)),
             )
             .add_systems(OnExit(GameState::MainMenu), despawn_screen::<MenuWindow>);
     }
@@ -23,15 +30,24 @@ impl Plugin for MenuPlugin {
 pub struct MenuWindow;
 
 #[derive(Component)]
-struct StartButton;
+struct MenuTitle;
 
-#[derive(Component)]
-struct InfoButton;
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+enum MenuButton {
+    /// Straight into the first level that hasn't been won
+    Continue(usize),
+    Start,
+    Info,
+    Settings,
+}
 
 fn setup(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut bgm_query: Query<(&mut BGM, Entity)>,
+    progress: Res<LevelProgress>,
+    campaigns: Res<Campaigns>,
+    selected: Res<SelectedCampaign>,
+    settings: Res<GameSettings>,
+    mut music: EventWriter<PlayMusic>,
 ) {
     commands.spawn((
         Camera2dBundle {
@@ -44,22 +60,14 @@ fn setup(
         MenuWindow,
     ));
 
-    if let Ok((mut bgm, entity)) = bgm_query.get_single_mut() {
-        if bgm.0 != "music/Main_menu.ogg" {
-            commands
-                .entity(entity)
-                .remove::<AudioSink>()
-                .insert(asset_server.load::<AudioSource>("music/Main_menu.ogg"));
-            bgm.0 = "music/Main_menu.ogg".to_string();
-        }
-    }
+    music.send(PlayMusic::looping("music/Main_menu.ogg"));
 
     commands.spawn((
         TextBundle {
             text: Text::from_section(
-                "EPIC GAME",
+                String::new(),
                 TextStyle {
-                    color: Color::PURPLE,
+                    color: settings.palette.colors().title,
                     font_size: 100.0,
                     ..default()
                 },
@@ -75,96 +83,76 @@ fn setup(
             },
             ..default()
         },
+        LocalizedText::key("menu.title"),
+        MenuTitle,
         MenuWindow,
     ));
 
-    commands
-        .spawn((
-            ButtonBundle {
-                style: Style {
+    // continues the campaign that was last picked on the level select screen
+    let continue_button = progress
+        .next_level(&campaigns[selected.0])
+        .map(|level| ("menu.continue", MenuButton::Continue(level)));
+    let buttons: Vec<_> = continue_button
+        .into_iter()
+        .chain([
+            ("menu.start", MenuButton::Start),
+            ("menu.info", MenuButton::Info),
+            ("common.settings", MenuButton::Settings),
+        ])
+        .collect();
+    // the buttons share the bottom 60% of the screen, however many there are
+    let spacing = 60.0 / buttons.len() as f32;
+    for (index, (key, button)) in buttons.into_iter().enumerate() {
+        let top = 40.0 + spacing * index as f32;
+        commands
+            .spawn_button(
+                LocalizedText::key(key),
+                60.0,
+                ButtonColors::MENU,
+                Style {
                     position_type: PositionType::Absolute,
-                    top: Val::Percent(40.0),
+                    top: Val::Percent(top),
                     justify_self: JustifySelf::Center,
                     min_width: Val::Percent(40.0),
                     min_height: Val::Px(20.0),
-                    justify_content: JustifyContent::Center,
                     ..default()
                 },
-                background_color: BackgroundColor(Color::PINK),
-                border_color: BorderColor(Color::PURPLE),
-                ..default()
-            },
-            StartButton,
-            MenuWindow,
-        ))
-        .with_children(|parent| {
-            parent.spawn((
-                TextBundle::from_section(
-                    "Start Game",
-                    TextStyle {
-                        font: default(),
-                        font_size: 60.0,
-                        color: Color::WHITE,
-                    },
-                )
-                .with_text_justify(JustifyText::Center),
-                StartButton,
-            ));
-        });
-
-    commands
-        .spawn((
-            ButtonBundle {
-                style: Style {
-                    position_type: PositionType::Absolute,
-                    top: Val::Percent(60.0),
-                    justify_self: JustifySelf::Center,
-                    min_width: Val::Percent(40.0),
-                    min_height: Val::Px(20.0),
-                    justify_content: JustifyContent::Center,
-                    ..default()
-                },
-                background_color: BackgroundColor(Color::PINK),
-                border_color: BorderColor(Color::PURPLE),
-                ..default()
-            },
-            InfoButton,
-            MenuWindow,
-        ))
-        .with_children(|parent| {
-            parent.spawn((
-                TextBundle::from_section(
-                    "Info",
-                    TextStyle {
-                        font: default(),
-                        font_size: 60.0,
-                        color: Color::WHITE,
-                    },
-                )
-                .with_text_justify(JustifyText::Center),
-                InfoButton,
-            ));
-        });
+            )
+            .insert((button, MenuWindow));
+    }
 }
 
-fn start_game(
-    text_selection: Query<&Interaction, With<StartButton>>,
+fn menu_button_pressed(
+    query: Query<(&Interaction, &MenuButton)>,
+    campaigns: Res<Campaigns>,
+    selected: Res<SelectedCampaign>,
+    mut scene: ResMut<LevelScene>,
     mut state: ResMut<NextState<GameState>>,
+    mut settings_state: ResMut<NextState<SettingsState>>,
 ) {
-    for selection in text_selection.iter() {
-        if *selection == Interaction::Pressed {
-            state.set(GameState::LevelSelect);
+    for (interaction, button) in query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match *button {
+            MenuButton::Continue(level) => {
+                if let Some(level) = campaigns.scene(selected.0, level) {
+                    *scene = level;
+                    state.set(GameState::Gaming);
+                }
+            }
+            MenuButton::Start => state.set(GameState::LevelSelect),
+            MenuButton::Info => state.set(GameState::InfoScreen),
+            MenuButton::Settings => settings_state.set(SettingsState::Open),
         }
     }
 }
 
-fn open_info(
-    text_selection: Query<&Interaction, With<InfoButton>>,
-    mut state: ResMut<NextState<GameState>>,
-) {
-    for selection in text_selection.iter() {
-        if *selection == Interaction::Pressed {
-            state.set(GameState::InfoScreen);
+/// The palette can be changed from the settings, which open on top of the menu
+fn recolor_title(settings: Res<GameSettings>, mut titles: Query<&mut Text, With<MenuTitle>>) {
+    for mut text in titles.iter_mut() {
+        for section in text.sections.iter_mut() {
+            section.style.color = settings.palette.colors().title;
         }
     }
 }
@@ -0,0 +1,166 @@
+use toml_edit::{value, DocumentMut};
+
+/// Version of the level format that `LevelScene` reads, written as `format_version` at the top
+/// of a level file. Files without one are from before it was added, version 1.
+pub const CURRENT_VERSION: i64 = 1;
+
+/// Upgrades a level file one version, the one at index `n` goes from version `n + 1` to `n + 2`
+type Migration = fn(&mut DocumentMut) -> Result<(), String>;
+
+/// A change to the level format adds its migration here and bumps [`CURRENT_VERSION`]
+const MIGRATIONS: [Migration; (CURRENT_VERSION - 1) as usize] = [];
+
+pub fn format_version(document: &DocumentMut) -> Result<Option<i64>, String> {
+    document
+        .get("format_version")
+        .map(|version| {
+            version
+                .as_integer()
+                .ok_or_else(|| "format_version is not a number".to_string())
+        })
+        .transpose()
+}
+
+/// Brings a level file up to [`CURRENT_VERSION`], keeping its comments and layout.
+/// Returns whether anything had to change.
+pub fn migrate(document: &mut DocumentMut) -> Result<bool, String> {
+    run_migrations(document, &MIGRATIONS)
+}
+
+/// The version after `migrations` is the current one
+fn run_migrations(document: &mut DocumentMut, migrations: &[Migration]) -> Result<bool, String> {
+    let current = migrations.len() as i64 + 1;
+    let written = format_version(document)?;
+    let version = written.unwrap_or(1);
+    if version > current {
+        return Err(format!(
+            "it is format_version {version}, made for a newer version of the game"
+        ));
+    }
+    if version < 1 {
+        return Err(format!("there is no format_version {version}"));
+    }
+    if written == Some(current) {
+        return Ok(false);
+    }
+    for (from, migration) in (1..).zip(migrations).skip(version as usize - 1) {
+        migration(document).map_err(|err| format!("upgrading from version {from}: {err}"))?;
+    }
+    document.insert("format_version", value(current));
+    document.sort_values_by(|a, _, b, _| {
+        (b.get() == "format_version").cmp(&(a.get() == "format_version"))
+    });
+    Ok(true)
+}
+
+/// The text of a level file at [`CURRENT_VERSION`]
+pub fn migrate_str(data: &str) -> Result<String, String> {
+    let mut document: DocumentMut = data.parse().map_err(|err| format!("{err}"))?;
+    migrate(&mut document)?;
+    Ok(document.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::campaign::parse_level;
+    use crate::tile::TileRegistry;
+
+    /// Stands in for a real migration, so the chain can be tested before there is one
+    fn rename_speed(document: &mut DocumentMut) -> Result<(), String> {
+        if let Some(speed) = document.remove("speed") {
+            if document.contains_key("pace") {
+                return Err("it has both speed and pace".to_string());
+            }
+            document.insert("pace", speed);
+        }
+        Ok(())
+    }
+
+    fn migrated_with(data: &str, migrations: &[Migration]) -> Result<String, String> {
+        let mut document: DocumentMut = data.parse().unwrap();
+        run_migrations(&mut document, migrations)?;
+        Ok(document.to_string())
+    }
+
+    #[test]
+    fn migrations_run_from_the_version_of_the_file() {
+        let data = "\
+# an old level
+level_name = \"Old\"
+speed = 100 # how fast
+";
+        assert_eq!(
+            migrated_with(data, &[rename_speed]).unwrap(),
+            "\
+format_version = 2
+# an old level
+level_name = \"Old\"
+pace = 100 # how fast
+"
+        );
+        let data = "format_version = 2\nspeed = 100\n";
+        assert_eq!(migrated_with(data, &[rename_speed]).unwrap(), data);
+    }
+
+    #[test]
+    fn failed_migrations_say_which_version() {
+        let data = "speed = 1\npace = 1\n";
+        let err = migrated_with(data, &[rename_speed]).unwrap_err();
+        assert!(err.contains("version 1"));
+    }
+
+    #[test]
+    fn current_version_is_left_alone() {
+        let data = format!("format_version = {CURRENT_VERSION} # checked\nlevel_name = \"New\"\n");
+        assert_eq!(migrate_str(&data).unwrap(), data);
+    }
+
+    #[test]
+    fn unversioned_files_get_a_version() {
+        let data = "level_name = \"Old\"\n";
+        let migrated = migrate_str(data).unwrap();
+        let document: DocumentMut = migrated.parse().unwrap();
+        assert_eq!(format_version(&document), Ok(Some(CURRENT_VERSION)));
+    }
+
+    #[test]
+    fn newer_versions_are_refused() {
+        let data = format!("format_version = {}\n", CURRENT_VERSION + 1);
+        assert!(migrate_str(&data).unwrap_err().contains("newer version"));
+    }
+
+    #[test]
+    fn versions_below_1_are_refused() {
+        assert!(migrate_str("format_version = 0\n").is_err());
+    }
+
+    #[test]
+    fn unversioned_level_loads() {
+        let data = "\
+level_name = \"The beginning\"
+background_texture = \"EvilGrass.png\"
+music = \"Battle_1.ogg\"
+points_of_interest = [[[5, 3], \"Chest\"], [[3, 1], \"Spike\"]]
+
+[[heros]]
+hero_type = \"JohnHeron\"
+targets = [[300, 150]]
+position = [-100, -100]
+speed = 100
+max_health = 100
+";
+        let scene = parse_level(data, &TileRegistry::built_in()).unwrap();
+        assert_eq!(scene.format_version, CURRENT_VERSION);
+        assert_eq!(scene.heros.len(), 1);
+        assert_eq!(scene.points_of_interest_map.len(), 2);
+    }
+
+    #[test]
+    fn built_in_levels_are_current() {
+        for data in crate::campaign::BUILT_IN_LEVELS {
+            let document: DocumentMut = data.parse().unwrap();
+            assert_eq!(format_version(&document), Ok(Some(CURRENT_VERSION)));
+        }
+    }
+}
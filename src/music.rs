@@ -0,0 +1,257 @@
+use std::time::Duration;
+
+use bevy::audio::{PlaybackMode, Volume};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::GameSettings;
+
+pub struct MusicPlugin;
+
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MusicState>()
+            .init_resource::<MusicMood>()
+            .add_event::<PlayMusic>()
+            .add_systems(
+                PostUpdate,
+                (
+                    resume_after_stinger,
+                    play_music,
+                    follow_mood.run_if(resource_changed::<MusicMood>),
+                    fade_music,
+                )
+                    .chain(),
+            );
+    }
+}
+
+const DEFAULT_FADE: Duration = Duration::from_millis(1000);
+const STINGER_FADE: Duration = Duration::from_millis(300);
+/// Switching layers is slower than switching tracks, so it doesn't draw attention
+const MOOD_FADE: Duration = Duration::from_millis(2000);
+
+/// Switch to another track, the old one fades out while the new one fades in
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct PlayMusic {
+    /// Path of the track in the assets
+    pub track: String,
+    pub fade: Duration,
+    pub kind: TrackKind,
+    /// Played in sync with the track, a layer replaces the track while its mood is on
+    pub layers: Vec<MusicLayer>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum TrackKind {
+    /// Repeats until something else is played
+    #[default]
+    Loop,
+    /// Plays once, after which the looping track from before comes back
+    Stinger,
+}
+
+/// How tense things are, picks which layer of the music is heard
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MusicMood {
+    #[default]
+    Calm,
+    Intense,
+}
+
+/// An alternative version of the music of a level, as given in the level file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MusicLayer {
+    pub track: String,
+    pub mood: MusicMood,
+}
+
+impl PlayMusic {
+    pub fn looping(track: impl Into<String>) -> Self {
+        Self {
+            track: track.into(),
+            fade: DEFAULT_FADE,
+            kind: TrackKind::Loop,
+            layers: Vec::new(),
+        }
+    }
+
+    pub fn stinger(track: impl Into<String>) -> Self {
+        Self {
+            track: track.into(),
+            fade: STINGER_FADE,
+            kind: TrackKind::Stinger,
+            layers: Vec::new(),
+        }
+    }
+
+    pub fn with_layers(self, layers: Vec<MusicLayer>) -> Self {
+        Self { layers, ..self }
+    }
+
+    /// Whether the part of this music that belongs to `layer` can be heard,
+    /// `None` being the track itself
+    fn audible(&self, layer: Option<MusicMood>, mood: MusicMood) -> bool {
+        match layer {
+            Some(layer) => layer == mood,
+            None => !self.layers.iter().any(|layer| layer.mood == mood),
+        }
+    }
+}
+
+#[derive(Resource, Debug, Default)]
+struct MusicState {
+    current: Option<PlayMusic>,
+    /// What to go back to once the stinger that's playing is done
+    resume: Option<PlayMusic>,
+}
+
+/// A playing track, despawned once it has faded out
+#[derive(Component, Debug)]
+struct Music {
+    kind: TrackKind,
+    /// The mood of the layer this track plays, `None` for the main track
+    layer: Option<MusicMood>,
+    /// Fading out to be despawned, instead of a layer that's just not heard right now
+    stopping: bool,
+    /// How loud the track is right now, from 0 to 1 on top of the music volume
+    level: f32,
+    target: f32,
+    /// Change in level per second
+    speed: f32,
+}
+
+impl Music {
+    fn fade_to(&mut self, target: f32, fade: Duration) {
+        self.target = target;
+        self.speed = if fade.is_zero() {
+            f32::INFINITY
+        } else {
+            (target - self.level).abs() / fade.as_secs_f32()
+        };
+    }
+}
+
+fn play_music(
+    mut commands: Commands,
+    mut events: EventReader<PlayMusic>,
+    asset_server: Res<AssetServer>,
+    settings: Res<GameSettings>,
+    mood: Res<MusicMood>,
+    mut state: ResMut<MusicState>,
+    mut playing: Query<&mut Music>,
+) {
+    for event in events.read() {
+        // screens that share their music just ask for it again
+        if event.kind == TrackKind::Loop && state.current.as_ref() == Some(event) {
+            continue;
+        }
+        match event.kind {
+            TrackKind::Loop => state.resume = None,
+            TrackKind::Stinger => {
+                if let Some(current) = state.current.take() {
+                    if current.kind == TrackKind::Loop {
+                        state.resume = Some(current);
+                    }
+                }
+            }
+        }
+        state.current = Some(event.clone());
+
+        for mut music in playing.iter_mut() {
+            music.stopping = true;
+            music.fade_to(0.0, event.fade);
+        }
+        let layers = event
+            .layers
+            .iter()
+            .map(|layer| (&layer.track, Some(layer.mood)));
+        // all layers start together so they stay in sync
+        for (track, layer) in [(&event.track, None)].into_iter().chain(layers) {
+            let target = if event.audible(layer, *mood) {
+                1.0
+            } else {
+                0.0
+            };
+            let mut music = Music {
+                kind: event.kind,
+                layer,
+                stopping: false,
+                level: 0.0,
+                target: 0.0,
+                speed: 0.0,
+            };
+            music.fade_to(target, event.fade);
+            if event.fade.is_zero() {
+                music.level = target;
+            }
+            commands.spawn((
+                AudioBundle {
+                    source: asset_server.load::<AudioSource>(track),
+                    settings: PlaybackSettings {
+                        mode: match event.kind {
+                            TrackKind::Loop => PlaybackMode::Loop,
+                            TrackKind::Stinger => PlaybackMode::Once,
+                        },
+                        volume: Volume::new(music.level * settings.music_volume),
+                        ..default()
+                    },
+                },
+                music,
+            ));
+        }
+    }
+}
+
+fn follow_mood(mood: Res<MusicMood>, state: Res<MusicState>, mut query: Query<&mut Music>) {
+    let Some(current) = &state.current else {
+        return;
+    };
+    for mut music in query.iter_mut().filter(|music| !music.stopping) {
+        let target = if current.audible(music.layer, *mood) {
+            1.0
+        } else {
+            0.0
+        };
+        music.fade_to(target, MOOD_FADE);
+    }
+}
+
+/// Uses real time, so music keeps fading while the game is paused
+fn fade_music(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    settings: Res<GameSettings>,
+    mut query: Query<(Entity, &mut Music, Option<&AudioSink>)>,
+) {
+    for (entity, mut music, sink) in query.iter_mut() {
+        let step = music.speed * time.delta_seconds();
+        music.level = if music.level < music.target {
+            (music.level + step).min(music.target)
+        } else {
+            (music.level - step).max(music.target)
+        };
+        if music.stopping && music.level <= 0.0 {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        if let Some(sink) = sink {
+            sink.set_volume(music.level * settings.music_volume);
+        }
+    }
+}
+
+fn resume_after_stinger(
+    query: Query<(&Music, &AudioSink)>,
+    mut state: ResMut<MusicState>,
+    mut events: EventWriter<PlayMusic>,
+) {
+    let stinger_done = query
+        .iter()
+        .any(|(music, sink)| music.kind == TrackKind::Stinger && !music.stopping && sink.empty());
+    if stinger_done {
+        if let Some(resume) = state.resume.take() {
+            events.send(resume);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The colours used to tell things apart, picked in the settings
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Palette {
+    #[default]
+    Standard,
+    /// Based on the Okabe-Ito colours, which stay apart with every common colour blindness
+    ColorBlind,
+    HighContrast,
+}
+
+impl Palette {
+    pub const ALL: [Palette; 3] = [
+        Palette::Standard,
+        Palette::ColorBlind,
+        Palette::HighContrast,
+    ];
+
+    pub fn colors(self) -> &'static PaletteColors {
+        match self {
+            Palette::Standard => &STANDARD,
+            Palette::ColorBlind => &COLOR_BLIND,
+            Palette::HighContrast => &HIGH_CONTRAST,
+        }
+    }
+
+    /// Key of the name in the string files
+    pub fn key(self) -> &'static str {
+        match self {
+            Palette::Standard => "settings.palette.standard",
+            Palette::ColorBlind => "settings.palette.color_blind",
+            Palette::HighContrast => "settings.palette.high_contrast",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PaletteColors {
+    /// Border of the selected spell and game speed
+    pub selected: Color,
+    pub unselected: Color,
+    /// Ring around the button that has the keyboard focus
+    pub focus: Color,
+    /// Title of the main menu
+    pub title: Color,
+    pub level_won: Color,
+    pub level_not_won: Color,
+    pub level_text: Color,
+    pub health: Color,
+    pub health_missing: Color,
+    /// Timeline of a hero on the loss screen
+    pub survived: Color,
+    pub died: Color,
+    pub win: Color,
+    pub loss: Color,
+    pub warning: Color,
+}
+
+const STANDARD: PaletteColors = PaletteColors {
+    selected: Color::ORANGE_RED,
+    unselected: Color::WHITE,
+    focus: Color::ORANGE_RED,
+    title: Color::PURPLE,
+    level_won: Color::GOLD,
+    level_not_won: Color::WHITE,
+    level_text: Color::PURPLE,
+    health: Color::FUCHSIA,
+    health_missing: Color::RED,
+    survived: Color::FUCHSIA,
+    died: Color::RED,
+    win: Color::GREEN,
+    loss: Color::RED,
+    warning: Color::ORANGE_RED,
+};
+
+const ORANGE: Color = Color::rgb(0.9, 0.6, 0.0);
+const SKY_BLUE: Color = Color::rgb(0.35, 0.7, 0.9);
+const BLUISH_GREEN: Color = Color::rgb(0.0, 0.6, 0.5);
+const YELLOW: Color = Color::rgb(0.95, 0.9, 0.25);
+const BLUE: Color = Color::rgb(0.0, 0.45, 0.7);
+const VERMILLION: Color = Color::rgb(0.8, 0.4, 0.0);
+
+const COLOR_BLIND: PaletteColors = PaletteColors {
+    selected: ORANGE,
+    unselected: Color::WHITE,
+    focus: YELLOW,
+    title: Color::BLACK,
+    level_won: SKY_BLUE,
+    level_not_won: Color::WHITE,
+    level_text: Color::BLACK,
+    health: BLUE,
+    health_missing: Color::DARK_GRAY,
+    survived: BLUE,
+    died: VERMILLION,
+    win: BLUISH_GREEN,
+    loss: VERMILLION,
+    warning: ORANGE,
+};
+
+const HIGH_CONTRAST: PaletteColors = PaletteColors {
+    selected: Color::YELLOW,
+    unselected: Color::BLACK,
+    focus: Color::YELLOW,
+    title: Color::BLACK,
+    level_won: Color::YELLOW,
+    level_not_won: Color::WHITE,
+    level_text: Color::BLACK,
+    health: Color::WHITE,
+    health_missing: Color::BLACK,
+    survived: Color::WHITE,
+    died: Color::YELLOW,
+    win: Color::WHITE,
+    loss: Color::YELLOW,
+    warning: Color::YELLOW,
+};
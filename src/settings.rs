@@ -0,0 +1,703 @@
+use bevy::audio::Volume;
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+use bevy::window::{PresentMode, PrimaryWindow, WindowMode};
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::despawn_screen;
+use crate::focus::{FocusScope, Focused};
+use crate::input::{ActionBindings, Binding, InputBindings, Wheel};
+use crate::locale::{Language, Locale, LocalizedText};
+use crate::palette::Palette;
+use crate::widget::{ButtonColors, SpawnButton};
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        // `GameSettings` is loaded in `main`, since the window needs it before the app starts
+        app.init_state::<SettingsState>()
+            .init_resource::<Rebinding>()
+            .init_resource::<SettingsPage>()
+            .add_systems(
+                OnEnter(SettingsState::Open),
+                (setup, update_option_list, update_binding_list, show_page).chain(),
+            )
+            .add_systems(
+                Update,
+                (
+                    capture_binding.before(settings_button_pressed),
+                    settings_button_pressed,
+                    close_settings_keybind,
+                    update_option_list.run_if(
+                        resource_changed::<GameSettings>.or_else(resource_changed::<Locale>),
+                    ),
+                    update_binding_list.run_if(
+                        resource_changed::<InputBindings>
+                            .or_else(resource_changed::<Rebinding>)
+                            .or_else(resource_changed::<Locale>)
+                            .or_else(resource_changed::<GameSettings>),
+                    ),
+                    show_page.run_if(resource_changed::<SettingsPage>),
+                )
+                    .run_if(in_state(SettingsState::Open)),
+            )
+            .add_systems(
+                Update,
+                apply_settings.run_if(resource_changed::<GameSettings>),
+            )
+            .add_systems(
+                OnExit(SettingsState::Open),
+                (despawn_screen::<SettingsWindow>, save_settings),
+            );
+    }
+}
+
+/// The settings overlay can be opened on top of any screen, so it has its own state
+#[derive(States, Default, Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub enum SettingsState {
+    #[default]
+    Closed,
+    Open,
+}
+
+/// Everything on the general page of the settings, saved to `config/settings.toml`
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameSettings {
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    pub fullscreen: bool,
+    pub vsync: bool,
+    pub ui_scale: f32,
+    /// Pause the level when the window loses focus
+    pub pause_on_focus_loss: bool,
+    pub language: Language,
+    pub palette: Palette,
+    /// Slow down the flickering flames of the firewall, the one effect in the game that flashes
+    pub reduce_flashing: bool,
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        Self {
+            music_volume: 1.0,
+            sfx_volume: 1.0,
+            fullscreen: false,
+            vsync: true,
+            ui_scale: 1.0,
+            pause_on_focus_loss: true,
+            language: Language::English,
+            palette: Palette::Standard,
+            reduce_flashing: false,
+        }
+    }
+}
+
+impl GameSettings {
+    pub const FILE_NAME: &'static str = "settings.toml";
+
+    pub fn present_mode(&self) -> PresentMode {
+        if self.vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        }
+    }
+
+    pub fn window_mode(&self) -> WindowMode {
+        if self.fullscreen {
+            WindowMode::BorderlessFullscreen
+        } else {
+            WindowMode::Windowed
+        }
+    }
+
+    /// Volume for a sound effect that is `base` loud at full volume
+    pub fn sfx(&self, base: f32) -> Volume {
+        Volume::new(base * self.sfx_volume)
+    }
+
+    fn describe(&self, option: GameOption, locale: &Locale) -> String {
+        let on_off = |value| {
+            locale
+                .get(if value { "settings.on" } else { "settings.off" })
+                .to_string()
+        };
+        let percentage = |value: f32| format!("{:.0}%", value * 100.0);
+        match option {
+            GameOption::MusicVolume => percentage(self.music_volume),
+            GameOption::SfxVolume => percentage(self.sfx_volume),
+            GameOption::Fullscreen => on_off(self.fullscreen),
+            GameOption::Vsync => on_off(self.vsync),
+            GameOption::UiScale => percentage(self.ui_scale),
+            GameOption::PauseOnFocusLoss => on_off(self.pause_on_focus_loss),
+            GameOption::Language => self.language.name().to_string(),
+            GameOption::Palette => locale.get(self.palette.key()).to_string(),
+            GameOption::ReduceFlashing => on_off(self.reduce_flashing),
+        }
+    }
+
+    /// Steps the option up or down, on/off options flip either way
+    fn change(&mut self, option: GameOption, up: bool) {
+        let step = |value: f32, step: f32, min: f32, max: f32| {
+            let value = if up { value + step } else { value - step };
+            // round so repeated steps don't drift away from nice numbers
+            ((value / step).round() * step).clamp(min, max)
+        };
+        match option {
+            GameOption::MusicVolume => self.music_volume = step(self.music_volume, 0.1, 0.0, 1.0),
+            GameOption::SfxVolume => self.sfx_volume = step(self.sfx_volume, 0.1, 0.0, 1.0),
+            GameOption::Fullscreen => self.fullscreen = !self.fullscreen,
+            GameOption::Vsync => self.vsync = !self.vsync,
+            GameOption::UiScale => self.ui_scale = step(self.ui_scale, 0.25, 0.5, 3.0),
+            GameOption::PauseOnFocusLoss => self.pause_on_focus_loss = !self.pause_on_focus_loss,
+            GameOption::Language => self.language = cycle(&Language::ALL, self.language, up),
+            GameOption::Palette => self.palette = cycle(&Palette::ALL, self.palette, up),
+            GameOption::ReduceFlashing => self.reduce_flashing = !self.reduce_flashing,
+        }
+    }
+}
+
+/// The choice after or before `current`, wrapping around at the ends
+fn cycle<T: Copy + PartialEq>(choices: &[T], current: T, up: bool) -> T {
+    let index = choices
+        .iter()
+        .position(|&choice| choice == current)
+        .unwrap_or_default();
+    let next = if up {
+        index + 1
+    } else {
+        index + choices.len() - 1
+    };
+    choices[next % choices.len()]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GameOption {
+    MusicVolume,
+    SfxVolume,
+    Fullscreen,
+    Vsync,
+    UiScale,
+    PauseOnFocusLoss,
+    Language,
+    Palette,
+    ReduceFlashing,
+}
+
+impl GameOption {
+    const ALL: [GameOption; 9] = [
+        GameOption::MusicVolume,
+        GameOption::SfxVolume,
+        GameOption::Fullscreen,
+        GameOption::Vsync,
+        GameOption::UiScale,
+        GameOption::PauseOnFocusLoss,
+        GameOption::Language,
+        GameOption::Palette,
+        GameOption::ReduceFlashing,
+    ];
+
+    fn label(self) -> LocalizedText {
+        LocalizedText::key(match self {
+            GameOption::MusicVolume => "settings.option.music_volume",
+            GameOption::SfxVolume => "settings.option.sfx_volume",
+            GameOption::Fullscreen => "settings.option.fullscreen",
+            GameOption::Vsync => "settings.option.vsync",
+            GameOption::UiScale => "settings.option.ui_scale",
+            GameOption::PauseOnFocusLoss => "settings.option.pause_on_focus_loss",
+            GameOption::Language => "settings.option.language",
+            GameOption::Palette => "settings.option.palette",
+            GameOption::ReduceFlashing => "settings.option.reduce_flashing",
+        })
+    }
+}
+
+/// Annotate everything in the settings overlay with this component
+#[derive(Component)]
+pub struct SettingsWindow;
+
+#[derive(Resource, Component, Default, Debug, Clone, Copy, PartialEq)]
+enum SettingsPage {
+    #[default]
+    General,
+    Controls,
+}
+
+/// Only shown while its page is open
+#[derive(Component)]
+struct PageContent(SettingsPage);
+
+#[derive(Component)]
+struct OptionList;
+
+#[derive(Component)]
+struct BindingList;
+
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+enum SettingsButton {
+    Page(SettingsPage),
+    /// Step an option up (`true`) or down
+    Change(GameOption, bool),
+    /// Replace the input at this index in the bindings of the action
+    Rebind(usize, usize),
+    /// Add another input to the action at this index
+    AddBinding(usize),
+    /// Reset everything on the current page
+    Reset,
+    Back,
+}
+
+/// The binding waiting for the player to press something, `None` as input index means a new one
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub struct Rebinding(Option<(usize, Option<usize>)>);
+
+/// Run condition for things that shouldn't react to the input that is about to be bound
+pub fn is_rebinding(rebinding: Res<Rebinding>) -> bool {
+    rebinding.0.is_some()
+}
+
+fn setup(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(10.0),
+                    padding: UiRect::all(Val::Px(20.0)),
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.85).into(),
+                // the screen underneath shouldn't react while the settings are open
+                focus_policy: FocusPolicy::Block,
+                z_index: ZIndex::Global(20),
+                ..default()
+            },
+            SettingsWindow,
+            FocusScope(20),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    String::new(),
+                    TextStyle {
+                        font_size: 60.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ),
+                LocalizedText::key("common.settings"),
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        column_gap: Val::Px(20.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    spawn_text_button(
+                        parent,
+                        LocalizedText::key("settings.general"),
+                        SettingsButton::Page(SettingsPage::General),
+                    );
+                    spawn_text_button(
+                        parent,
+                        LocalizedText::key("settings.controls"),
+                        SettingsButton::Page(SettingsPage::Controls),
+                    );
+                });
+            let page_style = Style {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(10.0),
+                ..default()
+            };
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: page_style.clone(),
+                        ..default()
+                    },
+                    PageContent(SettingsPage::General),
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                display: Display::Grid,
+                                grid_template_columns: RepeatedGridTrack::auto(4),
+                                row_gap: Val::Px(4.0),
+                                column_gap: Val::Px(20.0),
+                                align_items: AlignItems::Center,
+                                justify_items: JustifyItems::Center,
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        OptionList,
+                    ));
+                });
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: page_style,
+                        ..default()
+                    },
+                    PageContent(SettingsPage::Controls),
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        TextBundle::from_section(
+                            String::new(),
+                            TextStyle {
+                                font_size: 20.0,
+                                color: Color::WHITE,
+                                ..default()
+                            },
+                        ),
+                        LocalizedText::key("settings.controls_hint"),
+                    ));
+                    // two actions next to each other, so all of them fit on the screen
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                display: Display::Grid,
+                                grid_template_columns: RepeatedGridTrack::auto(6),
+                                row_gap: Val::Px(4.0),
+                                column_gap: Val::Px(20.0),
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        BindingList,
+                    ));
+                });
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        column_gap: Val::Px(20.0),
+                        margin: UiRect::top(Val::Px(10.0)),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    spawn_text_button(
+                        parent,
+                        LocalizedText::key("settings.reset"),
+                        SettingsButton::Reset,
+                    );
+                    spawn_text_button(
+                        parent,
+                        LocalizedText::key("common.back"),
+                        SettingsButton::Back,
+                    );
+                });
+        });
+}
+
+fn spawn_text_button(
+    parent: &mut ChildBuilder,
+    text: impl Into<LocalizedText>,
+    button: SettingsButton,
+) -> Entity {
+    parent
+        .spawn_button(
+            text,
+            30.0,
+            ButtonColors::GAME,
+            Style {
+                padding: UiRect::horizontal(Val::Px(10.0)),
+                ..default()
+            },
+        )
+        .insert(button)
+        .id()
+}
+
+/// Shows the open page, and marks its button by giving it the menu colours
+fn show_page(
+    page: Res<SettingsPage>,
+    mut contents: Query<(&PageContent, &mut Style)>,
+    mut buttons: Query<(&SettingsButton, &mut ButtonColors)>,
+) {
+    for (content, mut style) in contents.iter_mut() {
+        style.display = if content.0 == *page {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+    for (button, mut colors) in buttons.iter_mut() {
+        if let SettingsButton::Page(button_page) = button {
+            *colors = if button_page == page.as_ref() {
+                ButtonColors::MENU
+            } else {
+                ButtonColors::GAME
+            };
+        }
+    }
+}
+
+fn update_option_list(
+    mut commands: Commands,
+    list: Query<Entity, With<OptionList>>,
+    buttons: Query<&SettingsButton>,
+    settings: Res<GameSettings>,
+    locale: Res<Locale>,
+    mut focused: ResMut<Focused>,
+) {
+    let Ok(list) = list.get_single() else {
+        return;
+    };
+    let focused_button = focused
+        .0
+        .and_then(|entity| buttons.get(entity).ok())
+        .copied();
+    let mut keep_focus = |button, entity| {
+        if Some(button) == focused_button {
+            focused.0 = Some(entity);
+        }
+    };
+    let label_style = TextStyle {
+        font_size: 25.0,
+        color: Color::WHITE,
+        ..default()
+    };
+    commands
+        .entity(list)
+        .despawn_descendants()
+        .with_children(|parent| {
+            for option in GameOption::ALL {
+                parent.spawn((
+                    TextBundle::from_section(String::new(), label_style.clone()),
+                    option.label(),
+                ));
+                let button = SettingsButton::Change(option, false);
+                keep_focus(button, spawn_text_button(parent, "<", button));
+                parent.spawn(TextBundle::from_section(
+                    settings.describe(option, &locale),
+                    label_style.clone(),
+                ));
+                let button = SettingsButton::Change(option, true);
+                keep_focus(button, spawn_text_button(parent, ">", button));
+            }
+        });
+}
+
+fn update_binding_list(
+    mut commands: Commands,
+    list: Query<Entity, With<BindingList>>,
+    buttons: Query<&SettingsButton>,
+    bindings: Res<InputBindings>,
+    rebinding: Res<Rebinding>,
+    locale: Res<Locale>,
+    settings: Res<GameSettings>,
+    mut focused: ResMut<Focused>,
+) {
+    let Ok(list) = list.get_single() else {
+        return;
+    };
+    // the list gets rebuilt, so the focus has to move to the new copy of the button
+    let focused_button = focused
+        .0
+        .and_then(|entity| buttons.get(entity).ok())
+        .copied();
+    let mut keep_focus = |button, entity| {
+        if Some(button) == focused_button {
+            focused.0 = Some(entity);
+        }
+    };
+    let label_style = TextStyle {
+        font_size: 25.0,
+        color: Color::WHITE,
+        ..default()
+    };
+    commands
+        .entity(list)
+        .despawn_descendants()
+        .with_children(|parent| {
+            for (action_index, ActionBindings { action, inputs }) in
+                bindings.bindings.iter().enumerate()
+            {
+                parent.spawn((
+                    TextBundle::from_section(String::new(), label_style.clone()),
+                    action.text(),
+                ));
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            column_gap: Val::Px(5.0),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        for (input_index, input) in inputs.iter().enumerate() {
+                            let text = if rebinding.0 == Some((action_index, Some(input_index))) {
+                                LocalizedText::key("settings.press_something")
+                            } else {
+                                input.text()
+                            };
+                            let button = SettingsButton::Rebind(action_index, input_index);
+                            keep_focus(button, spawn_text_button(parent, text, button));
+                        }
+                        let text = if rebinding.0 == Some((action_index, None)) {
+                            LocalizedText::key("settings.press_something")
+                        } else {
+                            "+".into()
+                        };
+                        let button = SettingsButton::AddBinding(action_index);
+                        keep_focus(button, spawn_text_button(parent, text, button));
+                    });
+
+                let conflicts = bindings.conflicts(*action);
+                let warning = if conflicts.is_empty() {
+                    String::new()
+                } else {
+                    let names: Vec<_> = conflicts
+                        .iter()
+                        .map(|action| locale.text(&action.text()))
+                        .collect();
+                    locale.format("settings.also_used_by", &[("actions", names.join(", "))])
+                };
+                parent.spawn(TextBundle::from_section(
+                    warning,
+                    TextStyle {
+                        color: settings.palette.colors().warning,
+                        ..label_style.clone()
+                    },
+                ));
+            }
+        });
+}
+
+fn settings_button_pressed(
+    query: Query<(&Interaction, &SettingsButton), Changed<Interaction>>,
+    mut settings: ResMut<GameSettings>,
+    mut bindings: ResMut<InputBindings>,
+    mut rebinding: ResMut<Rebinding>,
+    mut page: ResMut<SettingsPage>,
+    mut state: ResMut<NextState<SettingsState>>,
+) {
+    for (interaction, button) in query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match *button {
+            SettingsButton::Page(new_page) => {
+                *page = new_page;
+                rebinding.0 = None;
+            }
+            SettingsButton::Change(option, up) => settings.change(option, up),
+            SettingsButton::Rebind(action, input) => rebinding.0 = Some((action, Some(input))),
+            SettingsButton::AddBinding(action) => rebinding.0 = Some((action, None)),
+            SettingsButton::Reset => {
+                match *page {
+                    SettingsPage::General => *settings = GameSettings::default(),
+                    SettingsPage::Controls => *bindings = InputBindings::default(),
+                }
+                rebinding.0 = None;
+            }
+            SettingsButton::Back => {
+                rebinding.0 = None;
+                state.set(SettingsState::Closed);
+            }
+        }
+    }
+}
+
+/// Waits for the next key, mouse button, gamepad button or scroll after a control was clicked
+fn capture_binding(
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    mut wheel_events: EventReader<MouseWheel>,
+    mut bindings: ResMut<InputBindings>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    let wheel = Wheel::read(&mut wheel_events);
+    let Some((action_index, input_index)) = rebinding.0 else {
+        return;
+    };
+    if keys.just_pressed(KeyCode::Escape) {
+        rebinding.0 = None;
+        return;
+    }
+    let inputs = &mut bindings.bindings[action_index].inputs;
+    if keys.just_pressed(KeyCode::Delete) {
+        if let Some(input_index) = input_index {
+            inputs.remove(input_index);
+        }
+        rebinding.0 = None;
+        return;
+    }
+
+    let pressed = keys
+        .get_just_pressed()
+        .next()
+        .map(|&key| Binding::Key(key))
+        .or_else(|| {
+            mouse
+                .get_just_pressed()
+                .next()
+                .map(|&button| Binding::Mouse(button))
+        })
+        .or_else(|| {
+            gamepad_buttons
+                .get_just_pressed()
+                .next()
+                .map(|button| Binding::Gamepad(button.button_type))
+        })
+        .or((wheel.up > 0.0).then_some(Binding::WheelUp))
+        .or((wheel.down > 0.0).then_some(Binding::WheelDown));
+    let Some(pressed) = pressed else {
+        return;
+    };
+    match input_index {
+        Some(input_index) => inputs[input_index] = pressed,
+        None if !inputs.contains(&pressed) => inputs.push(pressed),
+        None => {}
+    }
+    rebinding.0 = None;
+}
+
+fn close_settings_keybind(
+    keys: Res<ButtonInput<KeyCode>>,
+    rebinding: Res<Rebinding>,
+    mut state: ResMut<NextState<SettingsState>>,
+) {
+    // escape while rebinding only cancels the rebinding
+    if keys.just_pressed(KeyCode::Escape) && rebinding.0.is_none() && !rebinding.is_changed() {
+        state.set(SettingsState::Closed);
+    }
+}
+
+fn save_settings(settings: Res<GameSettings>, bindings: Res<InputBindings>) {
+    config::save(GameSettings::FILE_NAME, settings.as_ref());
+    config::save(InputBindings::FILE_NAME, bindings.as_ref());
+}
+
+/// Runs whenever the settings change, so everything is applied live
+fn apply_settings(
+    settings: Res<GameSettings>,
+    mut window: Query<&mut Window, With<PrimaryWindow>>,
+    mut ui_scale: ResMut<UiScale>,
+) {
+    if let Ok(mut window) = window.get_single_mut() {
+        window.mode = settings.window_mode();
+        window.present_mode = settings.present_mode();
+    }
+    // the music volume is picked up by the music plugin every frame
+    ui_scale.0 = settings.ui_scale;
+}
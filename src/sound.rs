@@ -0,0 +1,118 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use bevy::audio::{PlaybackMode, SpatialScale};
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::game::damage::DamageKind;
+use crate::game::Spell;
+use crate::settings::GameSettings;
+
+pub struct SoundPlugin;
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SoundRegistry::built_in())
+            .add_event::<PlaySound>()
+            .add_systems(PostUpdate, play_sounds);
+    }
+}
+
+/// World units per unit of distance for the spatial audio, sounds at about this distance
+/// from the camera start getting quieter
+const HEARING_DISTANCE: f32 = 320.0;
+/// Distance between the ears of the camera, sounds further to the side than this are only
+/// heard on one side
+pub const EAR_GAP: f32 = 400.0;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SoundEffect {
+    pub file: String,
+    #[serde(default = "one")]
+    pub volume: f32,
+    #[serde(default = "one")]
+    pub speed: f32,
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+/// All sound effects by name, and which of them plays for what happens in the game,
+/// read from `sounds.toml`. Holds those of the campaign being played, with its own added.
+#[derive(Resource, Debug, Default, Clone, Deserialize)]
+pub struct SoundRegistry {
+    /// Effect played where a spell gets cast
+    #[serde(default)]
+    pub spells: HashMap<Spell, String>,
+    /// Effect played where a hero takes this kind of damage
+    #[serde(default)]
+    pub damage: HashMap<DamageKind, String>,
+    #[serde(flatten)]
+    pub effects: HashMap<String, SoundEffect>,
+}
+
+impl SoundRegistry {
+    pub fn built_in() -> Self {
+        let mut registry = Self::default();
+        registry.extend(include_str!("sounds.toml")).unwrap();
+        registry
+    }
+
+    /// Adds what is in a `sounds.toml`, replacing effects and events that are already known
+    pub fn extend(&mut self, data: &str) -> Result<(), toml::de::Error> {
+        let other: Self = toml::from_str(data)?;
+        self.spells.extend(other.spells);
+        self.damage.extend(other.damage);
+        self.effects.extend(other.effects);
+        Ok(())
+    }
+}
+
+/// Play the sound effect with this name from the registry
+#[derive(Event, Debug, Clone)]
+pub struct PlaySound {
+    pub name: Cow<'static, str>,
+    /// Where in the world the sound comes from, `None` plays it the same in both ears
+    pub position: Option<Vec2>,
+}
+
+impl PlaySound {
+    pub fn at(name: impl Into<Cow<'static, str>>, position: Vec2) -> Self {
+        Self {
+            name: name.into(),
+            position: Some(position),
+        }
+    }
+}
+
+fn play_sounds(
+    mut commands: Commands,
+    mut events: EventReader<PlaySound>,
+    registry: Res<SoundRegistry>,
+    settings: Res<GameSettings>,
+    asset_server: Res<AssetServer>,
+) {
+    for event in events.read() {
+        let Some(effect) = registry.effects.get(event.name.as_ref()) else {
+            warn!("no sound effect called {:?} in sounds.toml", event.name);
+            continue;
+        };
+        let position = event.position.unwrap_or_default();
+        commands.spawn((
+            AudioBundle {
+                source: asset_server.load(&effect.file),
+                settings: PlaybackSettings {
+                    mode: PlaybackMode::Despawn,
+                    volume: settings.sfx(effect.volume),
+                    speed: effect.speed,
+                    spatial: event.position.is_some(),
+                    spatial_scale: Some(SpatialScale::new_2d(1.0 / HEARING_DISTANCE)),
+                    ..default()
+                },
+            },
+            TransformBundle::from_transform(Transform::from_translation(position.extend(0.0))),
+        ));
+    }
+}
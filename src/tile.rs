@@ -1,40 +1,168 @@
+use crate::campaign::Campaign;
+use crate::game::damage::DamageKind;
 use crate::game::GameWindow;
 use bevy::prelude::*;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Index;
 
-#[derive(Resource, Debug, Default, Serialize, Deserialize, Component, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
-pub enum Tile {
+/// A kind of tile in the [`TileRegistry`] of the campaign that is played
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Tile(usize);
+
+/// What a hero bumps into on the tile
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Collision {
+    #[default]
+    None,
+    /// Heroes are pushed back off the tile, like by a pole
+    Solid,
+}
+
+/// When stepping on the tile does its damage and sound
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Trigger {
+    /// The first time each hero steps on it
     #[default]
-    Ground,
-    Chest,
-    Grass,
-    Spike,
-    Hole,
-    Pole,
-}
-
-pub fn make_tile(tile: Tile, position: IVec2, commands: &mut Commands, asset_server: &AssetServer) {
-    let texture: Handle<Image> = asset_server.load(match tile {
-        Tile::Grass => "EvilGrass.png",
-        Tile::Spike => "Spikes.png",
-        Tile::Hole => "Pit.png",
-        Tile::Pole => "WoodPole.png",
-        Tile::Chest => "Chest.png",
-        _ => "test.png",
-    });
-    commands.spawn((
-        SpriteBundle {
-            texture,
-            transform: Transform {
-                translation: Vec3::from((grid_to_world(position), 0.2)),
-                scale: Vec3::splat(4.0),
+    Once,
+    /// Every time a hero steps on it again
+    Repeating,
+}
+
+/// How a tile looks and what it does to heroes, one entry in `tiles.toml`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TileKind {
+    #[serde(skip)]
+    pub name: String,
+    pub sprite: Option<String>,
+    pub collision: Collision,
+    pub damage: f32,
+    pub damage_kind: DamageKind,
+    /// Multiplies the speed of heroes walking over the tile
+    pub speed: f32,
+    pub trigger: Trigger,
+    /// Name of the sound effect played when the tile triggers
+    pub sound: Option<String>,
+    /// Colour of the tile on the map previews in level select
+    #[serde(deserialize_with = "hex_color")]
+    pub minimap_color: Color,
+}
+
+impl TileKind {
+    /// Whether stepping on the tile does anything besides bumping into it
+    pub fn has_behaviour(&self) -> bool {
+        self.damage > 0.0 || self.sound.is_some()
+    }
+}
+
+impl Default for TileKind {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            sprite: None,
+            collision: Collision::None,
+            damage: 0.0,
+            damage_kind: DamageKind::Spike,
+            speed: 1.0,
+            trigger: Trigger::Once,
+            sound: None,
+            minimap_color: Color::GRAY,
+        }
+    }
+}
+
+fn hex_color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+    let hex = String::deserialize(deserializer)?;
+    Color::hex(&hex).map_err(serde::de::Error::custom)
+}
+
+/// Every kind of tile by name, read from `tiles.toml` and the one of a level pack
+#[derive(Debug, Clone, Default)]
+pub struct TileRegistry {
+    kinds: Vec<TileKind>,
+    names: HashMap<String, Tile>,
+}
+
+impl TileRegistry {
+    pub fn built_in() -> Self {
+        let mut registry = Self::default();
+        registry.extend(include_str!("tiles.toml")).unwrap();
+        registry
+    }
+
+    /// Adds the tiles in a `tiles.toml`, a tile with a name that is already known replaces it
+    pub fn extend(&mut self, data: &str) -> Result<(), toml::de::Error> {
+        let kinds: BTreeMap<String, TileKind> = toml::from_str(data)?;
+        for (name, kind) in kinds {
+            let kind = TileKind {
+                name: name.clone(),
+                ..kind
+            };
+            match self.names.get(&name) {
+                Some(tile) => self.kinds[tile.0] = kind,
+                None => {
+                    self.names.insert(name, Tile(self.kinds.len()));
+                    self.kinds.push(kind);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<Tile> {
+        self.names.get(name).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &TileKind> {
+        self.kinds.iter()
+    }
+}
+
+impl Index<Tile> for TileRegistry {
+    type Output = TileKind;
+
+    fn index(&self, tile: Tile) -> &TileKind {
+        &self.kinds[tile.0]
+    }
+}
+
+/// The entity spawned for every point of interest, so damage can be traced back to its tile
+#[derive(Resource, Debug, Default, Deref, DerefMut)]
+pub struct TileEntities(pub HashMap<IVec2, Entity>);
+
+pub fn make_tile(
+    tile: Tile,
+    position: IVec2,
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    campaign: &Campaign,
+) -> Entity {
+    let texture = campaign.tiles[tile]
+        .sprite
+        .as_ref()
+        .map(|sprite| asset_server.load(campaign.asset_path(sprite)));
+    commands
+        .spawn((
+            SpriteBundle {
+                // tiles without a sprite still get an entity, damage is traced back to it
+                visibility: if texture.is_some() {
+                    Visibility::Inherited
+                } else {
+                    Visibility::Hidden
+                },
+                texture: texture.unwrap_or_default(),
+                transform: Transform {
+                    translation: Vec3::from((grid_to_world(position), 0.2)),
+                    scale: Vec3::splat(4.0),
+                    ..default()
+                },
                 ..default()
             },
-            ..default()
-        },
-        tile,
-        GameWindow,
-    ));
+            tile,
+            GameWindow,
+        ))
+        .id()
 }
 pub fn grid_tile(position: Vec2, grid: Vec<Vec<Tile>>) -> Option<Tile> {
     let position = world_to_grid(position);
@@ -0,0 +1,637 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+
+use bevy::prelude::*;
+use serde::de::{IgnoredAny, IntoDeserializer};
+use serde::Deserialize;
+use xml::reader::{EventReader, XmlEvent};
+
+use crate::campaign::prepare_level;
+use crate::game::damage::Resistances;
+use crate::game::hero::{HealthBar, Hero, HeroType};
+use crate::migration::CURRENT_VERSION;
+use crate::tile::TileRegistry;
+use crate::{LevelName, LevelScene, PointOfInterest};
+
+/// Tiled keeps the flips and rotations of a tile in the top bits of its id
+const FLIP_FLAGS: u32 = 0xF000_0000;
+/// World units per tile, as in `tile::grid_to_world`
+const TILE_SIZE: f32 = 64.0;
+
+/// Maps made in the Tiled editor, saved as XML (`.tmx`) or JSON (`.tmj`)
+pub fn is_tiled_map(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|extension| extension.to_str()),
+        Some("tmx" | "tmj")
+    )
+}
+
+/// Reads a Tiled map as a level:
+/// - tiles with a `tile` property are placed as the tile of that name, others are left out
+/// - objects of class `hero` are where heroes start, with `hero_type`, `speed`, `max_health`,
+///   `fire_resistance` and `spike_resistance` properties. Their `targets` property points to a
+///   polyline with the targets in order.
+/// - the `name` (or `name_key` for one in the string files), `music` and `background`
+///   properties of the map are those of the level
+///
+/// The map has to be finite, with its tile layers saved as CSV.
+pub fn import(path: &Path, tiles: &TileRegistry) -> Result<LevelScene, String> {
+    let read = |path: &Path| {
+        std::fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))
+    };
+    let mut map = if path.extension().is_some_and(|extension| extension == "tmx") {
+        parse_tmx(&read(path)?)?
+    } else {
+        parse_tmj(&read(path)?)?
+    };
+    let dir = path.parent().unwrap_or(Path::new(""));
+    for tileset in map.tilesets.iter_mut() {
+        if let Some(source) = tileset.source.take() {
+            let data = read(&dir.join(&source))?;
+            let external = if source.ends_with(".tsx") {
+                tileset_from_xml(&parse_xml(&data)?)?
+            } else {
+                serde_json::from_str(&data).map_err(|err| format!("{source}: {err}"))?
+            };
+            tileset.tiles = external.tiles;
+        }
+    }
+    prepare_level(map.to_scene()?, tiles)
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TiledMap {
+    width: i32,
+    height: i32,
+    tilewidth: f32,
+    tileheight: f32,
+    #[serde(default)]
+    infinite: bool,
+    #[serde(default)]
+    properties: Vec<Property>,
+    #[serde(default)]
+    tilesets: Vec<Tileset>,
+    #[serde(default)]
+    layers: Vec<Layer>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Tileset {
+    #[serde(default)]
+    firstgid: u32,
+    /// File of a tileset that is kept outside the map
+    source: Option<String>,
+    #[serde(default)]
+    tiles: Vec<TilesetTile>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TilesetTile {
+    id: u32,
+    #[serde(default)]
+    properties: Vec<Property>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct Property {
+    name: String,
+    value: PropertyValue,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+enum PropertyValue {
+    Bool(bool),
+    /// Also the id of the object an object property points to
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+enum Layer {
+    #[serde(rename = "tilelayer")]
+    Tiles {
+        #[serde(default)]
+        name: String,
+        data: LayerData,
+    },
+    #[serde(rename = "objectgroup")]
+    Objects {
+        #[serde(default)]
+        objects: Vec<Object>,
+    },
+    #[serde(rename = "group")]
+    Group {
+        #[serde(default)]
+        layers: Vec<Layer>,
+    },
+    /// Image layers have nothing for the level
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum LayerData {
+    /// Row by row from the top left, 0 is no tile
+    Ids(Vec<u32>),
+    /// Base64, maybe compressed
+    Encoded(IgnoredAny),
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Object {
+    id: i64,
+    /// Called `class` in some versions of Tiled
+    #[serde(default, rename = "type", alias = "class")]
+    class: String,
+    x: f32,
+    y: f32,
+    /// Relative to `x` and `y`
+    polyline: Option<Vec<Point>>,
+    #[serde(default)]
+    properties: Vec<Property>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct Point {
+    x: f32,
+    y: f32,
+}
+
+fn property<'a>(properties: &'a [Property], name: &str) -> Option<&'a PropertyValue> {
+    properties
+        .iter()
+        .find(|property| property.name == name)
+        .map(|property| &property.value)
+}
+
+fn text_property<'a>(properties: &'a [Property], name: &str) -> Option<&'a str> {
+    match property(properties, name)? {
+        PropertyValue::Text(text) => Some(text),
+        _ => None,
+    }
+}
+
+fn number_property(properties: &[Property], name: &str) -> Option<f32> {
+    match property(properties, name)? {
+        PropertyValue::Int(number) => Some(*number as f32),
+        PropertyValue::Float(number) => Some(*number as f32),
+        _ => None,
+    }
+}
+
+impl TiledMap {
+    /// Tiled has y pointing down and measures objects in pixels from the top left
+    fn to_world(&self, x: f32, y: f32) -> Vec2 {
+        Vec2::new(x / self.tilewidth - 0.5, 0.5 - y / self.tileheight) * TILE_SIZE
+    }
+
+    fn to_scene(&self) -> Result<LevelScene, String> {
+        if self.infinite {
+            return Err("infinite maps can't be imported, turn that off in the map".to_string());
+        }
+        let names: HashMap<u32, &str> = self
+            .tilesets
+            .iter()
+            .flat_map(|tileset| {
+                tileset.tiles.iter().filter_map(|tile| {
+                    let name = text_property(&tile.properties, "tile")?;
+                    Some((tileset.firstgid + tile.id, name))
+                })
+            })
+            .collect();
+
+        let mut points_of_interest = Vec::new();
+        let mut objects = Vec::new();
+        self.read_layers(&self.layers, &names, &mut points_of_interest, &mut objects)?;
+
+        let heros = objects
+            .iter()
+            .filter(|object| object.class.eq_ignore_ascii_case("hero"))
+            .map(|hero| self.hero(hero, &objects))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let required = |name| {
+            text_property(&self.properties, name)
+                .map(str::to_string)
+                .ok_or_else(|| format!("the map needs a {name} property"))
+        };
+        let level_name = match text_property(&self.properties, "name_key") {
+            Some(key) => LevelName::Key {
+                key: key.to_string(),
+            },
+            None => LevelName::Literal(required("name")?),
+        };
+        Ok(LevelScene {
+            format_version: CURRENT_VERSION,
+            level_name,
+            background_texture: required("background")?,
+            music: required("music")?,
+            heros,
+            points_of_interest,
+            ..default()
+        })
+    }
+
+    /// In order, so tiles on higher layers win
+    fn read_layers<'a>(
+        &self,
+        layers: &'a [Layer],
+        names: &HashMap<u32, &str>,
+        points_of_interest: &mut Vec<PointOfInterest>,
+        objects: &mut Vec<&'a Object>,
+    ) -> Result<(), String> {
+        for layer in layers {
+            match layer {
+                Layer::Tiles { name, data } => {
+                    let LayerData::Ids(ids) = data else {
+                        return Err(format!("layer {name:?} has to be saved as CSV"));
+                    };
+                    let width = usize::try_from(self.width).unwrap_or(0);
+                    let size = usize::try_from(self.height)
+                        .ok()
+                        .and_then(|height| width.checked_mul(height));
+                    if size != Some(ids.len()) {
+                        return Err(format!("layer {name:?} is not as big as the map"));
+                    }
+                    for (index, id) in ids.iter().enumerate() {
+                        if let Some(name) = names.get(&(id & !FLIP_FLAGS)) {
+                            let column = (index % width) as i32;
+                            let row = (index / width) as i32;
+                            let position = IVec2::new(column, -row);
+                            points_of_interest
+                                .push(PointOfInterest::Tile(position, name.to_string()));
+                        }
+                    }
+                }
+                Layer::Objects { objects: layer } => objects.extend(layer),
+                Layer::Group { layers } => {
+                    self.read_layers(layers, names, points_of_interest, objects)?
+                }
+                Layer::Other => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn hero(&self, object: &Object, objects: &[&Object]) -> Result<Hero, String> {
+        let properties = &object.properties;
+        let hero_type = match text_property(properties, "hero_type") {
+            Some(name) => HeroType::deserialize(name.into_deserializer())
+                .map_err(|err: serde::de::value::Error| format!("hero {}: {err}", object.id))?,
+            None => HeroType::default(),
+        };
+        let position = self.to_world(object.x, object.y);
+        let Some(PropertyValue::Int(targets)) = property(properties, "targets") else {
+            return Err(format!("hero {} has no targets property", object.id));
+        };
+        let polyline = objects
+            .iter()
+            .find(|object| object.id == *targets)
+            .and_then(|line| Some((line, line.polyline.as_ref()?)))
+            .ok_or_else(|| format!("the targets of hero {} are not a polyline", object.id))?;
+        let (line, points) = polyline;
+        let mut targets: Vec<_> = points
+            .iter()
+            .map(|point| self.to_world(line.x + point.x, line.y + point.y))
+            .collect();
+        // lines are usually drawn from the hero, that isn't a target
+        if targets
+            .first()
+            .is_some_and(|first| first.distance(position) < TILE_SIZE / 2.0)
+        {
+            targets.remove(0);
+        }
+        let max_health = number_property(properties, "max_health").unwrap_or(100.0);
+        Ok(Hero {
+            targets,
+            position,
+            speed: number_property(properties, "speed").unwrap_or(100.0),
+            hero_type,
+            health_bar: HealthBar {
+                max_health,
+                current_health: max_health,
+            },
+            resistances: Resistances {
+                fire: number_property(properties, "fire_resistance").unwrap_or_default(),
+                spike: number_property(properties, "spike_resistance").unwrap_or_default(),
+            },
+            ..default()
+        })
+    }
+}
+
+fn parse_tmj(data: &str) -> Result<TiledMap, String> {
+    serde_json::from_str(data).map_err(|err| err.to_string())
+}
+
+fn parse_tmx(data: &str) -> Result<TiledMap, String> {
+    let map = parse_xml(data)?;
+    if map.name != "map" {
+        return Err("this is not a Tiled map".to_string());
+    }
+    Ok(TiledMap {
+        width: map.parse("width")?,
+        height: map.parse("height")?,
+        tilewidth: map.parse("tilewidth")?,
+        tileheight: map.parse("tileheight")?,
+        infinite: map.attribute("infinite") == Some("1"),
+        properties: properties_from_xml(&map),
+        tilesets: map
+            .children("tileset")
+            .map(|tileset| {
+                Ok(Tileset {
+                    firstgid: tileset.parse("firstgid")?,
+                    source: tileset.attribute("source").map(str::to_string),
+                    ..tileset_from_xml(tileset)?
+                })
+            })
+            .collect::<Result<_, String>>()?,
+        layers: layers_from_xml(&map)?,
+    })
+}
+
+/// Just enough of XML for Tiled maps
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    attributes: HashMap<String, String>,
+    children: Vec<Element>,
+    text: String,
+}
+
+impl Element {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.get(name).map(String::as_str)
+    }
+
+    fn parse<T: FromStr>(&self, name: &str) -> Result<T, String> {
+        self.attribute(name)
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| format!("<{}> needs a {name}", self.name))
+    }
+
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+}
+
+fn parse_xml(data: &str) -> Result<Element, String> {
+    let mut stack = vec![Element::default()];
+    for event in EventReader::new(data.as_bytes()) {
+        match event.map_err(|err| err.to_string())? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => stack.push(Element {
+                name: name.local_name,
+                attributes: attributes
+                    .into_iter()
+                    .map(|attribute| (attribute.name.local_name, attribute.value))
+                    .collect(),
+                ..default()
+            }),
+            XmlEvent::EndElement { .. } => {
+                let element = stack.pop().ok_or("unbalanced XML")?;
+                stack
+                    .last_mut()
+                    .ok_or("unbalanced XML")?
+                    .children
+                    .push(element);
+            }
+            XmlEvent::Characters(text) | XmlEvent::CData(text) => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(&text);
+                }
+            }
+            _ => {}
+        }
+    }
+    stack
+        .pop()
+        .and_then(|document| document.children.into_iter().next())
+        .ok_or_else(|| "the file is empty".to_string())
+}
+
+fn properties_from_xml(element: &Element) -> Vec<Property> {
+    element
+        .children("properties")
+        .flat_map(|properties| properties.children("property"))
+        .map(|property| {
+            let text = property.attribute("value").unwrap_or(&property.text);
+            let value = match property.attribute("type") {
+                Some("bool") => PropertyValue::Bool(text == "true"),
+                Some("int" | "object") => PropertyValue::Int(text.parse().unwrap_or_default()),
+                Some("float") => PropertyValue::Float(text.parse().unwrap_or_default()),
+                _ => PropertyValue::Text(text.to_string()),
+            };
+            Property {
+                name: property.attribute("name").unwrap_or_default().to_string(),
+                value,
+            }
+        })
+        .collect()
+}
+
+fn tileset_from_xml(tileset: &Element) -> Result<Tileset, String> {
+    Ok(Tileset {
+        tiles: tileset
+            .children("tile")
+            .map(|tile| {
+                Ok(TilesetTile {
+                    id: tile.parse("id")?,
+                    properties: properties_from_xml(tile),
+                })
+            })
+            .collect::<Result<_, String>>()?,
+        ..default()
+    })
+}
+
+fn layers_from_xml(parent: &Element) -> Result<Vec<Layer>, String> {
+    parent
+        .children
+        .iter()
+        .map(|layer| {
+            Ok(match layer.name.as_str() {
+                "layer" => {
+                    let name = layer.attribute("name").unwrap_or_default().to_string();
+                    let data = layer
+                        .children("data")
+                        .next()
+                        .ok_or_else(|| format!("layer {name:?} has no data"))?;
+                    let data = match data.attribute("encoding") {
+                        Some("csv") => LayerData::Ids(
+                            data.text
+                                .split(',')
+                                .map(|id| {
+                                    id.trim().parse().map_err(|_| format!("{id:?} is no tile"))
+                                })
+                                .collect::<Result<_, _>>()?,
+                        ),
+                        None => LayerData::Ids(
+                            data.children("tile")
+                                .map(|tile| tile.parse("gid").unwrap_or_default())
+                                .collect(),
+                        ),
+                        Some(_) => LayerData::Encoded(IgnoredAny),
+                    };
+                    Layer::Tiles { name, data }
+                }
+                "objectgroup" => Layer::Objects {
+                    objects: layer
+                        .children("object")
+                        .map(object_from_xml)
+                        .collect::<Result<_, _>>()?,
+                },
+                "group" => Layer::Group {
+                    layers: layers_from_xml(layer)?,
+                },
+                _ => Layer::Other,
+            })
+        })
+        .filter(|layer| !matches!(layer, Ok(Layer::Other)))
+        .collect()
+}
+
+fn object_from_xml(object: &Element) -> Result<Object, String> {
+    let polyline = match object.children("polyline").next() {
+        Some(polyline) => Some(
+            polyline
+                .attribute("points")
+                .unwrap_or_default()
+                .split_whitespace()
+                .map(|point| {
+                    let (x, y) = point
+                        .split_once(',')
+                        .ok_or("a polyline point needs an x and y")?;
+                    let coordinate = |value: &str| {
+                        value
+                            .parse()
+                            .map_err(|_| format!("{value:?} in a polyline is no number"))
+                    };
+                    Ok(Point {
+                        x: coordinate(x)?,
+                        y: coordinate(y)?,
+                    })
+                })
+                .collect::<Result<_, String>>()?,
+        ),
+        None => None,
+    };
+    Ok(Object {
+        id: object.parse("id")?,
+        class: object
+            .attribute("class")
+            .or(object.attribute("type"))
+            .unwrap_or_default()
+            .to_string(),
+        x: object.parse("x")?,
+        y: object.parse("y")?,
+        polyline,
+        properties: properties_from_xml(object),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TMJ: &str = r#"{
+        "width": 3, "height": 2, "tilewidth": 16, "tileheight": 16, "infinite": false,
+        "properties": [
+            {"name": "name", "type": "string", "value": "Imported"},
+            {"name": "music", "type": "string", "value": "Battle_1.ogg"},
+            {"name": "background", "type": "string", "value": "EvilGrass.png"}
+        ],
+        "tilesets": [{"firstgid": 1, "tiles": [
+            {"id": 0, "properties": [{"name": "tile", "type": "string", "value": "Pole"}]},
+            {"id": 1, "properties": [{"name": "tile", "type": "string", "value": "Spike"}]}
+        ]}],
+        "layers": [
+            {"type": "tilelayer", "name": "tiles", "data": [1, 0, 2, 0, 2147483649, 3]},
+            {"type": "objectgroup", "objects": [
+                {"id": 1, "type": "hero", "x": 8, "y": 24, "properties": [
+                    {"name": "hero_type", "type": "string", "value": "RerinGuard"},
+                    {"name": "max_health", "type": "int", "value": 60},
+                    {"name": "targets", "type": "object", "value": 2}
+                ]},
+                {"id": 2, "type": "", "x": 8, "y": 24, "polyline": [{"x": 0, "y": 0}, {"x": 32, "y": -16}]}
+            ]}
+        ]
+    }"#;
+
+    const TMX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="3" height="2" tilewidth="16" tileheight="16" infinite="0">
+ <properties>
+  <property name="name" value="Imported"/>
+  <property name="music" value="Battle_1.ogg"/>
+  <property name="background" value="EvilGrass.png"/>
+ </properties>
+ <tileset firstgid="1" name="tiles" tilewidth="16" tileheight="16">
+  <tile id="0"><properties><property name="tile" value="Pole"/></properties></tile>
+  <tile id="1"><properties><property name="tile" value="Spike"/></properties></tile>
+ </tileset>
+ <layer id="1" name="tiles" width="3" height="2">
+  <data encoding="csv">
+1,0,2,
+0,2147483649,3
+</data>
+ </layer>
+ <objectgroup id="2" name="heroes">
+  <object id="1" type="hero" x="8" y="24">
+   <properties>
+    <property name="hero_type" value="RerinGuard"/>
+    <property name="max_health" type="int" value="60"/>
+    <property name="targets" type="object" value="2"/>
+   </properties>
+  </object>
+  <object id="2" x="8" y="24"><polyline points="0,0 32,-16"/></object>
+ </objectgroup>
+</map>"#;
+
+    fn check(map: TiledMap) {
+        let scene = prepare_level(map.to_scene().unwrap(), &TileRegistry::built_in()).unwrap();
+        let tiles = TileRegistry::built_in();
+        let tile = |name| tiles.get(name).unwrap();
+        let expected = HashMap::from([
+            (IVec2::new(0, 0), tile("Pole")),
+            (IVec2::new(2, 0), tile("Spike")),
+            // flipped
+            (IVec2::new(1, -1), tile("Pole")),
+        ]);
+        assert_eq!(scene.points_of_interest_map, expected);
+        assert_eq!(scene.level_name, LevelName::Literal("Imported".to_string()));
+        let hero = &scene.heros[0];
+        assert!(matches!(hero.hero_type, HeroType::RerinGuard));
+        assert_eq!(hero.health_bar.max_health, 60.0);
+        assert_eq!(hero.position, Vec2::new(0.0, -64.0));
+        assert_eq!(hero.targets, vec![Vec2::new(128.0, 0.0)]);
+    }
+
+    #[test]
+    fn tmj_map_imports() {
+        check(parse_tmj(TMJ).unwrap());
+    }
+
+    #[test]
+    fn tmx_map_imports() {
+        check(parse_tmx(TMX).unwrap());
+    }
+
+    #[test]
+    fn layers_have_to_fill_the_map() {
+        let smaller = TMJ.replace("2147483649, 3]", "2147483649]");
+        assert!(parse_tmj(&smaller).unwrap().to_scene().is_err());
+        let huge = TMJ.replace(
+            r#""width": 3, "height": 2"#,
+            r#""width": 2147483647, "height": 2147483647"#,
+        );
+        assert!(parse_tmj(&huge).unwrap().to_scene().is_err());
+        let negative = TMJ.replace(r#""width": 3, "height": 2"#, r#""width": -3, "height": -2"#);
+        assert!(parse_tmj(&negative).unwrap().to_scene().is_err());
+    }
+}
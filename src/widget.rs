@@ -0,0 +1,135 @@
+use bevy::ecs::system::EntityCommands;
+use bevy::prelude::*;
+
+use crate::focus::Focusable;
+use crate::locale::LocalizedText;
+
+pub struct WidgetPlugin;
+
+impl Plugin for WidgetPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, update_button_colors);
+    }
+}
+
+/// Colours of a menu button, the background gets darker when hovered or pressed
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct ButtonColors {
+    pub background: Color,
+    pub text: Color,
+}
+
+impl ButtonColors {
+    /// Main menu, info screen and level select
+    pub const MENU: Self = Self {
+        background: Color::PINK,
+        text: Color::WHITE,
+    };
+    /// Everything shown while in a level
+    pub const GAME: Self = Self {
+        background: Color::FUCHSIA,
+        text: Color::WHITE,
+    };
+
+    pub fn background(&self, interaction: Interaction) -> Color {
+        let shade = match interaction {
+            Interaction::Pressed => 0.7,
+            Interaction::Hovered => 0.85,
+            Interaction::None => 1.0,
+        };
+        let [red, green, blue, alpha] = self.background.as_rgba_f32();
+        Color::rgba(red * shade, green * shade, blue * shade, alpha)
+    }
+}
+
+/// Spawns the buttons used by all menus, works on both `Commands` and `ChildBuilder`
+pub trait SpawnButton {
+    /// A focusable button with a single line of text, insert a marker on the result to find it back
+    fn spawn_button(
+        &mut self,
+        text: impl Into<LocalizedText>,
+        font_size: f32,
+        colors: ButtonColors,
+        style: Style,
+    ) -> EntityCommands<'_>;
+}
+
+/// A focusable button without any content, for buttons that hold more than a line of text
+pub fn button_bundle(colors: ButtonColors, style: Style) -> impl Bundle {
+    (
+        ButtonBundle {
+            style: Style {
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..style
+            },
+            background_color: colors.background.into(),
+            ..default()
+        },
+        colors,
+        Focusable,
+    )
+}
+
+fn button_text(
+    text: impl Into<LocalizedText>,
+    font_size: f32,
+    colors: ButtonColors,
+) -> impl Bundle {
+    (
+        TextBundle::from_section(
+            String::new(),
+            TextStyle {
+                font_size,
+                color: colors.text,
+                ..default()
+            },
+        )
+        .with_text_justify(JustifyText::Center)
+        .with_no_wrap(),
+        text.into(),
+    )
+}
+
+impl SpawnButton for Commands<'_, '_> {
+    fn spawn_button(
+        &mut self,
+        text: impl Into<LocalizedText>,
+        font_size: f32,
+        colors: ButtonColors,
+        style: Style,
+    ) -> EntityCommands<'_> {
+        let mut button = self.spawn(button_bundle(colors, style));
+        button.with_children(|parent| {
+            parent.spawn(button_text(text, font_size, colors));
+        });
+        button
+    }
+}
+
+impl SpawnButton for ChildBuilder<'_> {
+    fn spawn_button(
+        &mut self,
+        text: impl Into<LocalizedText>,
+        font_size: f32,
+        colors: ButtonColors,
+        style: Style,
+    ) -> EntityCommands<'_> {
+        let mut button = self.spawn(button_bundle(colors, style));
+        button.with_children(|parent| {
+            parent.spawn(button_text(text, font_size, colors));
+        });
+        button
+    }
+}
+
+fn update_button_colors(
+    mut query: Query<
+        (&Interaction, &ButtonColors, &mut BackgroundColor),
+        Or<(Changed<Interaction>, Changed<ButtonColors>)>,
+    >,
+) {
+    for (interaction, colors, mut background) in query.iter_mut() {
+        *background = colors.background(*interaction).into();
+    }
+}